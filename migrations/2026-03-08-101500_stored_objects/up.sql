@@ -0,0 +1,12 @@
+-- Your SQL goes here
+
+CREATE TABLE "stored_objects"(
+	"key" TEXT NOT NULL PRIMARY KEY,
+	"kind" TEXT NOT NULL,
+	"activity_id" INT8,
+	"content_type" TEXT NOT NULL,
+	"size_bytes" INT8 NOT NULL,
+	"created_at" TIMESTAMP NOT NULL
+);
+
+CREATE INDEX "stored_objects_activity_id_idx" ON "stored_objects" ("activity_id");
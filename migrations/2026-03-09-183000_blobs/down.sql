@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS "blob_refs";
+DROP TABLE IF EXISTS "blobs";
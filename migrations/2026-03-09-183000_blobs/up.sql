@@ -0,0 +1,14 @@
+-- Your SQL goes here
+
+CREATE TABLE "blobs"(
+	"hash" TEXT NOT NULL PRIMARY KEY,
+	"size_bytes" INT8 NOT NULL,
+	"created_at" TIMESTAMP NOT NULL
+);
+
+CREATE TABLE "blob_refs"(
+	"hash" TEXT NOT NULL REFERENCES "blobs" ("hash") ON DELETE CASCADE,
+	"owner" TEXT NOT NULL,
+	"created_at" TIMESTAMP NOT NULL,
+	PRIMARY KEY ("hash", "owner")
+);
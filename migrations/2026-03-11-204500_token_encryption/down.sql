@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "token" ALTER COLUMN "expires_in" TYPE INT4;
+ALTER TABLE "token" ALTER COLUMN "expires_at" TYPE INT4;
+ALTER TABLE "token" DROP CONSTRAINT "token_pkey";
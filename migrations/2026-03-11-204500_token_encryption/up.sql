@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- refresh_token and access_token hold values sealed by crypto::TokenCipher from now on
+
+ALTER TABLE "token" ADD PRIMARY KEY ("id");
+ALTER TABLE "token" ALTER COLUMN "expires_at" TYPE INT8;
+ALTER TABLE "token" ALTER COLUMN "expires_in" TYPE INT8;
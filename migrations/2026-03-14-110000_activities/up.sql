@@ -0,0 +1,20 @@
+-- Your SQL goes here
+
+CREATE TABLE "activities"(
+	"id" INT8 NOT NULL PRIMARY KEY,
+	"athlete_id" INT8 NOT NULL,
+	"name" TEXT NOT NULL,
+	"sport_type" TEXT,
+	"distance" REAL NOT NULL,
+	"moving_time" INT4 NOT NULL,
+	"elapsed_time" INT4 NOT NULL,
+	"total_elevation_gain" REAL,
+	"start_date" TIMESTAMPTZ NOT NULL,
+	"gear_id" TEXT,
+	"commute" BOOL NOT NULL DEFAULT FALSE,
+	"trainer" BOOL NOT NULL DEFAULT FALSE,
+	"created_at" TIMESTAMP NOT NULL,
+	"updated_at" TIMESTAMP NOT NULL
+);
+
+CREATE INDEX "activities_athlete_id_start_date_idx" ON "activities" ("athlete_id", "start_date");
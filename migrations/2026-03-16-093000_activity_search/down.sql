@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS "activities_search_vector_idx";
+ALTER TABLE "activities" DROP COLUMN "search_vector";
+ALTER TABLE "activities" DROP COLUMN "detail_synced_at";
+ALTER TABLE "activities" DROP COLUMN "private_note";
+ALTER TABLE "activities" DROP COLUMN "description";
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+
+-- Only the detailed activity has these, the list endpoint leaves them out
+ALTER TABLE "activities" ADD COLUMN "description" TEXT;
+ALTER TABLE "activities" ADD COLUMN "private_note" TEXT;
+ALTER TABLE "activities" ADD COLUMN "detail_synced_at" TIMESTAMP;
+
+ALTER TABLE "activities" ADD COLUMN "search_vector" TSVECTOR GENERATED ALWAYS AS (
+	setweight(to_tsvector('english', coalesce("name", '')), 'A') ||
+	setweight(to_tsvector('english', coalesce("description", '')), 'B') ||
+	setweight(to_tsvector('english', coalesce("private_note", '')), 'B')
+) STORED;
+
+CREATE INDEX "activities_search_vector_idx" ON "activities" USING GIN ("search_vector");
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+ALTER TABLE "activities" ADD COLUMN "calories" REAL;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS "best_efforts";
+ALTER TABLE "activities" DROP COLUMN "analyzed_at";
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+
+-- Set once the streams of the activity went through the analysis
+ALTER TABLE "activities" ADD COLUMN "analyzed_at" TIMESTAMP;
+
+CREATE TABLE "best_efforts"(
+	"activity_id" INT8 NOT NULL REFERENCES "activities" ("id") ON DELETE CASCADE,
+	"kind" TEXT NOT NULL,
+	"name" TEXT NOT NULL,
+	"athlete_id" INT8 NOT NULL,
+	"sport_type" TEXT,
+	"value" REAL NOT NULL,
+	"start_offset" INT4 NOT NULL,
+	"start_date" TIMESTAMPTZ NOT NULL,
+	"created_at" TIMESTAMP NOT NULL,
+	PRIMARY KEY ("activity_id", "kind", "name")
+);
+
+CREATE INDEX "best_efforts_athlete_id_kind_name_idx" ON "best_efforts" ("athlete_id", "kind", "name");
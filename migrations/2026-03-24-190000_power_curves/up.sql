@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE "power_curve_points"(
+	"activity_id" INT8 NOT NULL REFERENCES "activities" ("id") ON DELETE CASCADE,
+	"duration" INT4 NOT NULL,
+	"watts" REAL NOT NULL,
+	"athlete_id" INT8 NOT NULL,
+	"start_date" TIMESTAMPTZ NOT NULL,
+	PRIMARY KEY ("activity_id", "duration")
+);
+
+CREATE INDEX "power_curve_points_athlete_id_start_date_idx" ON "power_curve_points" ("athlete_id", "start_date");
+
+-- Analyze everything again on the next sync to fill the curves in
+UPDATE "activities" SET "analyzed_at" = NULL;
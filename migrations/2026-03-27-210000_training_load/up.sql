@@ -0,0 +1,23 @@
+-- Your SQL goes here
+
+-- Each row holds from its date until the next one
+CREATE TABLE "athlete_thresholds"(
+	"athlete_id" INT8 NOT NULL,
+	"effective_from" DATE NOT NULL,
+	"max_hr" INT4,
+	"resting_hr" INT4,
+	"ftp" INT4,
+	"created_at" TIMESTAMP NOT NULL,
+	PRIMARY KEY ("athlete_id", "effective_from")
+);
+
+CREATE TABLE "activity_loads"(
+	"activity_id" INT8 NOT NULL PRIMARY KEY REFERENCES "activities" ("id") ON DELETE CASCADE,
+	"athlete_id" INT8 NOT NULL,
+	"start_date" TIMESTAMPTZ NOT NULL,
+	"trimp" REAL,
+	"tss" REAL,
+	"load" REAL NOT NULL
+);
+
+CREATE INDEX "activity_loads_athlete_id_start_date_idx" ON "activity_loads" ("athlete_id", "start_date");
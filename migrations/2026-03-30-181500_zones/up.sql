@@ -0,0 +1,27 @@
+-- Your SQL goes here
+
+-- Zones set by the athlete ("custom") or fetched from strava ("strava")
+CREATE TABLE "athlete_zones"(
+	"athlete_id" INT8 NOT NULL,
+	"kind" TEXT NOT NULL,
+	"source" TEXT NOT NULL,
+	"zone" INT4 NOT NULL,
+	"min" INT4 NOT NULL,
+	"max" INT4 NOT NULL,
+	PRIMARY KEY ("athlete_id", "kind", "source", "zone")
+);
+
+CREATE TABLE "activity_zone_times"(
+	"activity_id" INT8 NOT NULL REFERENCES "activities" ("id") ON DELETE CASCADE,
+	"kind" TEXT NOT NULL,
+	"zone" INT4 NOT NULL,
+	"seconds" INT4 NOT NULL,
+	"athlete_id" INT8 NOT NULL,
+	"start_date" TIMESTAMPTZ NOT NULL,
+	PRIMARY KEY ("activity_id", "kind", "zone")
+);
+
+CREATE INDEX "activity_zone_times_athlete_id_start_date_idx" ON "activity_zone_times" ("athlete_id", "start_date");
+
+-- Analyze everything again on the next sync to fill the zone times in
+UPDATE "activities" SET "analyzed_at" = NULL;
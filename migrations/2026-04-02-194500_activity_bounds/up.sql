@@ -0,0 +1,18 @@
+-- Your SQL goes here
+
+-- Where each activity went, so the heatmap only reads the streams that touch a tile
+CREATE TABLE "activity_bounds"(
+	"activity_id" INT8 NOT NULL PRIMARY KEY REFERENCES "activities" ("id") ON DELETE CASCADE,
+	"athlete_id" INT8 NOT NULL,
+	"sport_type" TEXT,
+	"start_date" TIMESTAMPTZ NOT NULL,
+	"min_lat" FLOAT8 NOT NULL,
+	"min_lng" FLOAT8 NOT NULL,
+	"max_lat" FLOAT8 NOT NULL,
+	"max_lng" FLOAT8 NOT NULL
+);
+
+CREATE INDEX "activity_bounds_athlete_id_start_date_idx" ON "activity_bounds" ("athlete_id", "start_date");
+
+-- Analyze everything again on the next sync to fill the bounds in
+UPDATE "activities" SET "analyzed_at" = NULL;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "activities" DROP COLUMN "track_polyline";
+ALTER TABLE "activities" DROP COLUMN "polyline";
+ALTER TABLE "activities" DROP COLUMN "summary_polyline";
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+-- The maps strava gives with the summary and the detailed activity, and the track from
+-- the streams once they are analyzed, all as encoded polylines
+ALTER TABLE "activities" ADD COLUMN "summary_polyline" TEXT;
+ALTER TABLE "activities" ADD COLUMN "polyline" TEXT;
+ALTER TABLE "activities" ADD COLUMN "track_polyline" TEXT;
+
+-- Analyze everything again on the next sync to fill the tracks in
+UPDATE "activities" SET "analyzed_at" = NULL;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS "activities_gear_id_idx";
+DROP TABLE IF EXISTS "gear_components";
+DROP TABLE IF EXISTS "gear";
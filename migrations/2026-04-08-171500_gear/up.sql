@@ -0,0 +1,34 @@
+-- Your SQL goes here
+
+-- Bikes and shoes as strava has them, "distance" is strava's own count in meters
+CREATE TABLE "gear"(
+	"id" TEXT NOT NULL PRIMARY KEY,
+	"athlete_id" INT8 NOT NULL,
+	"kind" TEXT NOT NULL,
+	"name" TEXT,
+	"brand_name" TEXT,
+	"model_name" TEXT,
+	"description" TEXT,
+	"is_primary" BOOL NOT NULL,
+	"retired" BOOL NOT NULL,
+	"distance" FLOAT4 NOT NULL,
+	"updated_at" TIMESTAMP NOT NULL
+);
+
+CREATE INDEX "gear_athlete_id_idx" ON "gear" ("athlete_id");
+
+-- Parts of a gear tracked by the athlete, service intervals in meters and seconds of moving time
+CREATE TABLE "gear_components"(
+	"id" SERIAL PRIMARY KEY,
+	"gear_id" TEXT NOT NULL REFERENCES "gear" ("id") ON DELETE CASCADE,
+	"kind" TEXT NOT NULL,
+	"name" TEXT,
+	"installed_at" DATE NOT NULL,
+	"removed_at" DATE,
+	"service_distance" FLOAT4,
+	"service_time" INT4,
+	"created_at" TIMESTAMP NOT NULL
+);
+
+CREATE INDEX "gear_components_gear_id_idx" ON "gear_components" ("gear_id");
+CREATE INDEX "activities_gear_id_idx" ON "activities" ("gear_id");
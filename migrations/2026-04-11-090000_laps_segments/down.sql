@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS "segment_efforts";
+DROP TABLE IF EXISTS "segments";
+DROP TABLE IF EXISTS "activity_splits";
+DROP TABLE IF EXISTS "activity_laps";
@@ -0,0 +1,82 @@
+-- Your SQL goes here
+
+CREATE TABLE "activity_laps"(
+	"activity_id" INT8 NOT NULL REFERENCES "activities" ("id") ON DELETE CASCADE,
+	"lap_index" INT4 NOT NULL,
+	"id" INT8 NOT NULL,
+	"name" TEXT,
+	"elapsed_time" INT4 NOT NULL,
+	"moving_time" INT4 NOT NULL,
+	"start_date" TIMESTAMPTZ NOT NULL,
+	"distance" FLOAT4 NOT NULL,
+	"start_index" INT4,
+	"end_index" INT4,
+	"total_elevation_gain" FLOAT4,
+	"average_speed" FLOAT4,
+	"max_speed" FLOAT4,
+	"average_heartrate" FLOAT4,
+	"max_heartrate" FLOAT4,
+	"average_watts" FLOAT4,
+	"average_cadence" FLOAT4,
+	PRIMARY KEY ("activity_id", "lap_index")
+);
+
+-- Kilometer splits are "metric", mile splits "standard"
+CREATE TABLE "activity_splits"(
+	"activity_id" INT8 NOT NULL REFERENCES "activities" ("id") ON DELETE CASCADE,
+	"units" TEXT NOT NULL,
+	"split" INT4 NOT NULL,
+	"distance" FLOAT4 NOT NULL,
+	"elapsed_time" INT4 NOT NULL,
+	"moving_time" INT4 NOT NULL,
+	"elevation_difference" FLOAT4,
+	"average_speed" FLOAT4,
+	"average_grade_adjusted_speed" FLOAT4,
+	"average_heartrate" FLOAT4,
+	"pace_zone" INT4,
+	PRIMARY KEY ("activity_id", "units", "split")
+);
+
+CREATE TABLE "segments"(
+	"id" INT8 NOT NULL PRIMARY KEY,
+	"name" TEXT NOT NULL,
+	"activity_type" TEXT,
+	"distance" FLOAT4 NOT NULL,
+	"average_grade" FLOAT4,
+	"maximum_grade" FLOAT4,
+	"elevation_high" FLOAT4,
+	"elevation_low" FLOAT4,
+	"start_lat" FLOAT8,
+	"start_lng" FLOAT8,
+	"end_lat" FLOAT8,
+	"end_lng" FLOAT8,
+	"climb_category" INT4,
+	"city" TEXT,
+	"state" TEXT,
+	"country" TEXT,
+	"private" BOOL NOT NULL,
+	"updated_at" TIMESTAMP NOT NULL
+);
+
+CREATE TABLE "segment_efforts"(
+	"id" INT8 NOT NULL PRIMARY KEY,
+	"activity_id" INT8 NOT NULL REFERENCES "activities" ("id") ON DELETE CASCADE,
+	"segment_id" INT8 NOT NULL REFERENCES "segments" ("id"),
+	"athlete_id" INT8 NOT NULL,
+	"name" TEXT NOT NULL,
+	"elapsed_time" INT4 NOT NULL,
+	"moving_time" INT4 NOT NULL,
+	"start_date" TIMESTAMPTZ NOT NULL,
+	"distance" FLOAT4 NOT NULL,
+	"start_index" INT4,
+	"end_index" INT4,
+	"average_watts" FLOAT4,
+	"average_heartrate" FLOAT4,
+	"max_heartrate" FLOAT4,
+	"kom_rank" INT4,
+	"pr_rank" INT4,
+	"hidden" BOOL NOT NULL
+);
+
+CREATE INDEX "segment_efforts_activity_id_idx" ON "segment_efforts" ("activity_id");
+CREATE INDEX "segment_efforts_segment_id_elapsed_time_idx" ON "segment_efforts" ("segment_id", "elapsed_time");
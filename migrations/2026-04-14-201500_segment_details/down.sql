@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS "segment_efforts_athlete_id_segment_id_idx";
+DROP TABLE IF EXISTS "starred_segments";
+ALTER TABLE "segments" DROP COLUMN "detail_synced_at";
+ALTER TABLE "segments" DROP COLUMN "star_count";
+ALTER TABLE "segments" DROP COLUMN "athlete_count";
+ALTER TABLE "segments" DROP COLUMN "effort_count";
+ALTER TABLE "segments" DROP COLUMN "polyline";
+ALTER TABLE "segments" DROP COLUMN "total_elevation_gain";
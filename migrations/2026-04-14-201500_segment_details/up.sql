@@ -0,0 +1,17 @@
+-- Your SQL goes here
+
+-- Filled in from /segments/{id}, null until the segment's details are fetched
+ALTER TABLE "segments" ADD COLUMN "total_elevation_gain" FLOAT4;
+ALTER TABLE "segments" ADD COLUMN "polyline" TEXT;
+ALTER TABLE "segments" ADD COLUMN "effort_count" INT4;
+ALTER TABLE "segments" ADD COLUMN "athlete_count" INT4;
+ALTER TABLE "segments" ADD COLUMN "star_count" INT4;
+ALTER TABLE "segments" ADD COLUMN "detail_synced_at" TIMESTAMP;
+
+CREATE TABLE "starred_segments"(
+	"athlete_id" INT8 NOT NULL,
+	"segment_id" INT8 NOT NULL REFERENCES "segments" ("id") ON DELETE CASCADE,
+	PRIMARY KEY ("athlete_id", "segment_id")
+);
+
+CREATE INDEX "segment_efforts_athlete_id_segment_id_idx" ON "segment_efforts" ("athlete_id", "segment_id");
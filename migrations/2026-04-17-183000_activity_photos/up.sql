@@ -0,0 +1,19 @@
+-- Your SQL goes here
+
+-- The bytes live in the blob store, "hash" stays null until the photo is downloaded
+CREATE TABLE "activity_photos"(
+	"unique_id" TEXT NOT NULL PRIMARY KEY,
+	"activity_id" INT8 NOT NULL REFERENCES "activities" ("id") ON DELETE CASCADE,
+	"caption" TEXT,
+	"lat" FLOAT8,
+	"lng" FLOAT8,
+	"created_at" TIMESTAMPTZ,
+	"source" INT4,
+	"width" INT4,
+	"height" INT4,
+	"hash" TEXT,
+	"content_type" TEXT,
+	"downloaded_at" TIMESTAMP
+);
+
+CREATE INDEX "activity_photos_activity_id_idx" ON "activity_photos" ("activity_id");
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "activities" DROP COLUMN "social_synced_at";
+DROP TABLE IF EXISTS "activity_kudos";
+DROP TABLE IF EXISTS "activity_comments";
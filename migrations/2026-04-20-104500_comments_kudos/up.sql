@@ -0,0 +1,25 @@
+-- Your SQL goes here
+
+-- Comments and kudos are only ever added, so they outlive the accounts that left them
+CREATE TABLE "activity_comments"(
+	"id" INT8 NOT NULL PRIMARY KEY,
+	"activity_id" INT8 NOT NULL REFERENCES "activities" ("id") ON DELETE CASCADE,
+	"athlete_id" INT8,
+	"firstname" TEXT,
+	"lastname" TEXT,
+	"text" TEXT NOT NULL,
+	"created_at" TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX "activity_comments_activity_id_idx" ON "activity_comments" ("activity_id");
+
+-- Kudoers come without an id, their name is all there is
+CREATE TABLE "activity_kudos"(
+	"activity_id" INT8 NOT NULL REFERENCES "activities" ("id") ON DELETE CASCADE,
+	"firstname" TEXT NOT NULL,
+	"lastname" TEXT NOT NULL,
+	"first_seen_at" TIMESTAMP NOT NULL,
+	PRIMARY KEY ("activity_id", "firstname", "lastname")
+);
+
+ALTER TABLE "activities" ADD COLUMN "social_synced_at" TIMESTAMP;
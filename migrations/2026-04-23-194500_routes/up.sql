@@ -0,0 +1,26 @@
+-- Your SQL goes here
+
+-- Routes are kept when they're deleted on strava. The gpx and tcx exports are in the
+-- blob store, fetched again when the route's updated_at moves past files_updated_at.
+CREATE TABLE "routes"(
+	"id" INT8 NOT NULL PRIMARY KEY,
+	"athlete_id" INT8 NOT NULL,
+	"name" TEXT NOT NULL,
+	"description" TEXT,
+	"distance" FLOAT4 NOT NULL,
+	"elevation_gain" FLOAT4,
+	"route_type" INT4,
+	"sub_type" INT4,
+	"private" BOOL NOT NULL,
+	"starred" BOOL NOT NULL,
+	"estimated_moving_time" INT4,
+	"summary_polyline" TEXT,
+	"polyline" TEXT,
+	"created_at" TIMESTAMPTZ NOT NULL,
+	"updated_at" TIMESTAMPTZ NOT NULL,
+	"gpx_hash" TEXT,
+	"tcx_hash" TEXT,
+	"files_updated_at" TIMESTAMPTZ
+);
+
+CREATE INDEX "routes_athlete_id_idx" ON "routes" ("athlete_id");
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS "club_activities";
+DROP TABLE IF EXISTS "club_members";
+DROP TABLE IF EXISTS "athlete_clubs";
+DROP TABLE IF EXISTS "clubs";
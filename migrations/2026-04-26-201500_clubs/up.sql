@@ -0,0 +1,52 @@
+-- Your SQL goes here
+
+CREATE TABLE "clubs"(
+	"id" INT8 NOT NULL PRIMARY KEY,
+	"name" TEXT NOT NULL,
+	"sport_type" TEXT,
+	"city" TEXT,
+	"state" TEXT,
+	"country" TEXT,
+	"private" BOOL NOT NULL,
+	"member_count" INT4,
+	"url" TEXT,
+	"description" TEXT,
+	"club_type" TEXT,
+	"updated_at" TIMESTAMP NOT NULL
+);
+
+CREATE TABLE "athlete_clubs"(
+	"athlete_id" INT8 NOT NULL,
+	"club_id" INT8 NOT NULL REFERENCES "clubs" ("id") ON DELETE CASCADE,
+	PRIMARY KEY ("athlete_id", "club_id")
+);
+
+-- Members have no id, like kudoers
+CREATE TABLE "club_members"(
+	"club_id" INT8 NOT NULL REFERENCES "clubs" ("id") ON DELETE CASCADE,
+	"firstname" TEXT NOT NULL,
+	"lastname" TEXT NOT NULL,
+	"membership" TEXT,
+	"admin" BOOL NOT NULL,
+	"owner" BOOL NOT NULL,
+	PRIMARY KEY ("club_id", "firstname", "lastname")
+);
+
+-- Club activities have neither id nor date. "key" is a hash of the club and everything
+-- strava sends, first_seen_at stands in for the date.
+CREATE TABLE "club_activities"(
+	"key" TEXT NOT NULL PRIMARY KEY,
+	"club_id" INT8 NOT NULL REFERENCES "clubs" ("id") ON DELETE CASCADE,
+	"firstname" TEXT NOT NULL,
+	"lastname" TEXT NOT NULL,
+	"name" TEXT NOT NULL,
+	"sport_type" TEXT,
+	"distance" FLOAT4 NOT NULL,
+	"moving_time" INT4 NOT NULL,
+	"elapsed_time" INT4 NOT NULL,
+	"total_elevation_gain" FLOAT4 NOT NULL,
+	"workout_type" INT4,
+	"first_seen_at" TIMESTAMP NOT NULL
+);
+
+CREATE INDEX "club_activities_club_id_first_seen_at_idx" ON "club_activities" ("club_id", "first_seen_at");
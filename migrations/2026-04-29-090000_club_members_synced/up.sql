@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+-- When the members were last fetched, a sync refreshes the ones fetched longest ago first
+ALTER TABLE "clubs" ADD COLUMN "members_synced_at" TIMESTAMP;
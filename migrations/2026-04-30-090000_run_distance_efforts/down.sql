@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+
+-- Nothing to undo, the deleted efforts are not computed anymore
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+-- Distance efforts are only computed for runs now, drop the ones other sports got before
+DELETE FROM "best_efforts" WHERE "kind" = 'distance'
+	AND ("sport_type" IS NULL OR "sport_type" NOT IN ('Run', 'TrailRun', 'VirtualRun'));
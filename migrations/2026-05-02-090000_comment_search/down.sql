@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS "activities_search_vector_idx";
+ALTER TABLE "activities" DROP COLUMN "search_vector";
+
+ALTER TABLE "activities" ADD COLUMN "search_vector" TSVECTOR GENERATED ALWAYS AS (
+	setweight(to_tsvector('english', coalesce("name", '')), 'A') ||
+	setweight(to_tsvector('english', coalesce("description", '')), 'B') ||
+	setweight(to_tsvector('english', coalesce("private_note", '')), 'B')
+) STORED;
+
+CREATE INDEX "activities_search_vector_idx" ON "activities" USING GIN ("search_vector");
+
+ALTER TABLE "activities" DROP COLUMN "comments_text";
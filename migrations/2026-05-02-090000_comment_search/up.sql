@@ -0,0 +1,22 @@
+-- Your SQL goes here
+
+-- Every comment of the activity, kept up to date by save_social so the search finds them
+ALTER TABLE "activities" ADD COLUMN "comments_text" TEXT;
+
+UPDATE "activities" SET "comments_text" = (
+	SELECT string_agg("text", E'\n' ORDER BY "created_at", "id") FROM "activity_comments"
+	WHERE "activity_comments"."activity_id" = "activities"."id"
+);
+
+-- A generated column can't be changed, it's dropped with its index and added again
+DROP INDEX IF EXISTS "activities_search_vector_idx";
+ALTER TABLE "activities" DROP COLUMN "search_vector";
+
+ALTER TABLE "activities" ADD COLUMN "search_vector" TSVECTOR GENERATED ALWAYS AS (
+	setweight(to_tsvector('english', coalesce("name", '')), 'A') ||
+	setweight(to_tsvector('english', coalesce("description", '')), 'B') ||
+	setweight(to_tsvector('english', coalesce("private_note", '')), 'B') ||
+	setweight(to_tsvector('english', coalesce("comments_text", '')), 'C')
+) STORED;
+
+CREATE INDEX "activities_search_vector_idx" ON "activities" USING GIN ("search_vector");
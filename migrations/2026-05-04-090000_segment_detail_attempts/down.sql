@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE "segments" DROP COLUMN "detail_missing";
+ALTER TABLE "segments" DROP COLUMN "detail_attempted_at";
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+-- When the detail was last asked for, failed lookups go to the back of the queue
+ALTER TABLE "segments" ADD COLUMN "detail_attempted_at" TIMESTAMP;
+-- Strava answered 404, the segment is private or deleted and isn't asked for again
+ALTER TABLE "segments" ADD COLUMN "detail_missing" BOOL NOT NULL DEFAULT false;
@@ -0,0 +1,16 @@
+-- This file should undo anything in `up.sql`
+
+-- The metrics are f32, json! writes them widened to f64
+CREATE OR REPLACE FUNCTION "pg_temp"."json_number"(value REAL) RETURNS TEXT AS $$
+	SELECT CASE WHEN value = trunc(value) THEN trunc(value)::BIGINT::TEXT || '.0' ELSE value::DOUBLE PRECISION::TEXT END
+$$ LANGUAGE SQL IMMUTABLE;
+
+UPDATE "club_activities" SET "key" = encode(sha256(convert_to(
+	'[' || "club_id" || ',' || to_json("firstname")::TEXT || ',' || to_json("lastname")::TEXT || ',' ||
+	to_json("name")::TEXT || ',' || coalesce(to_json("sport_type")::TEXT, 'null') || ',' ||
+	"pg_temp"."json_number"("distance") || ',' || "moving_time" || ',' || "elapsed_time" || ',' ||
+	"pg_temp"."json_number"("total_elevation_gain") || ',' || coalesce("workout_type"::TEXT, 'null') || ']',
+	'UTF8'
+)), 'hex');
+
+DROP FUNCTION "pg_temp"."json_number";
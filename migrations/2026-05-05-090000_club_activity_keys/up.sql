@@ -0,0 +1,32 @@
+-- Your SQL goes here
+
+-- The keys no longer hash the name and workout type, which can be edited. They're computed
+-- again the way ClubActivityRow::from_activity does it, from the json array serde_json
+-- writes. The metrics are f32, json! writes them widened to f64.
+CREATE OR REPLACE FUNCTION "pg_temp"."json_number"(value REAL) RETURNS TEXT AS $$
+	SELECT CASE WHEN value = trunc(value) THEN trunc(value)::BIGINT::TEXT || '.0' ELSE value::DOUBLE PRECISION::TEXT END
+$$ LANGUAGE SQL IMMUTABLE;
+
+CREATE TEMPORARY TABLE "club_activity_keys" AS
+SELECT "key" AS "old_key", encode(sha256(convert_to(
+	'[' || "club_id" || ',' || to_json("firstname")::TEXT || ',' || to_json("lastname")::TEXT || ',' ||
+	coalesce(to_json("sport_type")::TEXT, 'null') || ',' || "pg_temp"."json_number"("distance") || ',' ||
+	"moving_time" || ',' || "elapsed_time" || ',' || "pg_temp"."json_number"("total_elevation_gain") || ']',
+	'UTF8'
+)), 'hex') AS "new_key", "first_seen_at"
+FROM "club_activities";
+
+-- Activities that were renamed got a row per name, the first seen one stays
+DELETE FROM "club_activities" WHERE "key" IN (
+	SELECT "old_key" FROM (
+		SELECT "old_key", row_number() OVER (PARTITION BY "new_key" ORDER BY "first_seen_at", "old_key") AS "n"
+		FROM "club_activity_keys"
+	) AS "ranked" WHERE "n" > 1
+);
+
+UPDATE "club_activities" SET "key" = "new_key"
+FROM "club_activity_keys" WHERE "club_activities"."key" = "old_key";
+
+DROP TABLE "club_activity_keys";
+
+DROP FUNCTION "pg_temp"."json_number";
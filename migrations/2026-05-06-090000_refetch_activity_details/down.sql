@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+
+-- Nothing to undo, the details are fetched again either way
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+-- Activities whose details were synced before laps and segment efforts were stored have
+-- none of them, fetch their details again
+UPDATE "activities" SET "detail_synced_at" = NULL
+WHERE "detail_synced_at" IS NOT NULL
+	AND NOT EXISTS (SELECT 1 FROM "activity_laps" WHERE "activity_laps"."activity_id" = "activities"."id");
@@ -0,0 +1,198 @@
+use crate::strava::parsers::ActivityStreams;
+use serde::Serialize;
+
+// Same distances strava uses for run best efforts, in meters
+pub const DISTANCES: [(&str, f32); 7] = [
+    ("400m", 400.0),
+    ("1k", 1000.0),
+    ("1mi", 1609.344),
+    ("5k", 5000.0),
+    ("10k", 10000.0),
+    ("half_marathon", 21097.5),
+    ("marathon", 42195.0),
+];
+
+// Durations for the best average power and heart rate, in seconds
+pub const DURATIONS: [(&str, usize); 5] = [
+    ("5s", 5),
+    ("1min", 60),
+    ("5min", 300),
+    ("20min", 1200),
+    ("60min", 3600),
+];
+
+// Strava only has distance best efforts for these, a ride's 10k isn't a record
+const RUN_SPORTS: [&str; 3] = ["Run", "TrailRun", "VirtualRun"];
+
+// Samples further apart than this are a pause, the seconds in between count as zero
+const MAX_HOLD: usize = 5;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Effort {
+    // "distance", "power" or "heartrate"
+    pub kind: &'static str,
+    pub name: &'static str,
+    // Seconds for distances, watts or bpm for durations
+    pub value: f32,
+    // Seconds from the start of the activity to the start of the effort
+    pub start_offset: i32,
+}
+
+// Smaller is better for distances (a time), bigger for the power and heart rate averages
+pub fn is_better(kind: &str, value: f32, than: f32) -> bool {
+    match kind {
+        "distance" => value < than,
+        _ => value > than,
+    }
+}
+
+pub fn best_efforts(streams: &ActivityStreams, sport_type: Option<&str>) -> Vec<Effort> {
+    let mut efforts = Vec::new();
+    let Some(time) = streams.numbers("time") else {
+        return efforts;
+    };
+
+    let run = sport_type.is_some_and(|sport| RUN_SPORTS.contains(&sport));
+    if let (true, Some(distance)) = (run, streams.numbers("distance")) {
+        for (name, meters) in DISTANCES {
+            if let Some((seconds, start)) = fastest_distance(time, distance, meters) {
+                efforts.push(Effort { kind: "distance", name, value: seconds, start_offset: start as i32 });
+            }
+        }
+    }
+    for (kind, stream_type) in [("power", "watts"), ("heartrate", "heartrate")] {
+        let Some(values) = streams.numbers(stream_type) else {
+            continue;
+        };
+        let per_second = resample(time, values);
+        for (name, seconds) in DURATIONS {
+            if let Some((average, start)) = best_average(&per_second, seconds) {
+                efforts.push(Effort { kind, name, value: average, start_offset: start as i32 });
+            }
+        }
+    }
+    efforts
+}
+
+// Shortest time to cover `meters`, and when it started. The start is interpolated
+// between samples so the effort covers exactly the distance, as strava does.
+pub fn fastest_distance(time: &[f32], distance: &[f32], meters: f32) -> Option<(f32, f32)> {
+    let len = time.len().min(distance.len());
+    let mut best: Option<(f32, f32)> = None;
+    let mut i = 0;
+    for j in 0..len {
+        let start = distance[j] - meters;
+        if start < distance[0] {
+            continue;
+        }
+        while i + 1 < j && distance[i + 1] <= start {
+            i += 1;
+        }
+        let start_time = match distance[i + 1] > distance[i] {
+            true => time[i] + (start - distance[i]) / (distance[i + 1] - distance[i]) * (time[i + 1] - time[i]),
+            false => time[i],
+        };
+        let seconds = time[j] - start_time;
+        if best.is_none_or(|(fastest, _)| seconds < fastest) {
+            best = Some((seconds, start_time));
+        }
+    }
+    best
+}
+
+// One value per second. Strava's smart recording skips samples while nothing changes,
+// so short gaps repeat the last value, longer ones are stops and count as zero.
+pub fn resample(time: &[f32], values: &[f32]) -> Vec<f32> {
+    let len = time.len().min(values.len());
+    let Some(last) = time[..len].last() else {
+        return Vec::new();
+    };
+    let mut per_second = vec![0.0; *last as usize + 1];
+    for k in 0..len {
+        let from = time[k] as usize;
+        let until = match k + 1 < len {
+            true => (time[k + 1] as usize).min(from + MAX_HOLD),
+            false => from + 1,
+        };
+        for second in per_second.iter_mut().take(until).skip(from) {
+            *second = values[k];
+        }
+    }
+    per_second
+}
+
+// Best average over a window of `seconds`, and the second it starts at
+pub fn best_average(per_second: &[f32], seconds: usize) -> Option<(f32, usize)> {
+    if seconds == 0 || per_second.len() < seconds {
+        return None;
+    }
+    let mut sum: f64 = per_second[..seconds].iter().map(|v| *v as f64).sum();
+    let mut best = (sum, 0);
+    for start in 1..=per_second.len() - seconds {
+        sum += per_second[start + seconds - 1] as f64 - per_second[start - 1] as f64;
+        if sum > best.0 {
+            best = (sum, start);
+        }
+    }
+    Some(((best.0 / seconds as f64) as f32, best.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fastest_distance() {
+        // 4 m/s for 100 seconds with a 6 m/s stretch between seconds 50 and 60
+        let time: Vec<f32> = (0..=100).map(|t| t as f32).collect();
+        let mut distance = vec![0.0];
+        for t in 1..=100 {
+            let speed = if (51..=60).contains(&t) { 6.0 } else { 4.0 };
+            distance.push(distance[t - 1] + speed);
+        }
+        let (seconds, start) = fastest_distance(&time, &distance, 60.0).unwrap();
+        assert_eq!((seconds, start), (10.0, 50.0));
+
+        // The fast 60m plus 30m at 4 m/s, the start falls between two samples
+        let (seconds, _) = fastest_distance(&time, &distance, 90.0).unwrap();
+        assert!((seconds - 17.5).abs() < 0.01);
+        assert!(fastest_distance(&time, &distance, 1000.0).is_none());
+    }
+
+    #[test]
+    fn test_resample() {
+        // Samples at 0, 1, 3 and a stop from 4 to 20
+        let per_second = resample(&[0.0, 1.0, 3.0, 4.0, 20.0], &[100.0, 200.0, 300.0, 400.0, 500.0]);
+        assert_eq!(per_second.len(), 21);
+        assert_eq!(&per_second[..5], &[100.0, 200.0, 200.0, 300.0, 400.0]);
+        assert_eq!(per_second[8], 400.0);
+        assert_eq!(per_second[9], 0.0);
+        assert_eq!(per_second[20], 500.0);
+        assert!(resample(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_best_average() {
+        let per_second = [100.0, 100.0, 300.0, 300.0, 100.0];
+        assert_eq!(best_average(&per_second, 2), Some((300.0, 2)));
+        assert_eq!(best_average(&per_second, 5), Some((180.0, 0)));
+        assert_eq!(best_average(&per_second, 6), None);
+    }
+
+    #[test]
+    fn test_best_efforts() {
+        let streams = r#"[{"type": "time", "data": [0, 1, 2, 3, 4, 5, 6], "series_type": "time", "original_size": 7, "resolution": "high"},
+            {"type": "watts", "data": [150, 200, 250, 300, 350, 400, 100], "series_type": "time", "original_size": 7, "resolution": "high"},
+            {"type": "distance", "data": [0, 80, 160, 240, 320, 400, 480], "series_type": "time", "original_size": 7, "resolution": "high"}]"#;
+        let streams = ActivityStreams::parse(streams.as_bytes()).unwrap();
+        assert_eq!(best_efforts(&streams, Some("Run")), vec![
+            Effort { kind: "distance", name: "400m", value: 5.0, start_offset: 0 },
+            Effort { kind: "power", name: "5s", value: 300.0, start_offset: 1 },
+        ]);
+        assert_eq!(best_efforts(&streams, Some("Ride")), vec![
+            Effort { kind: "power", name: "5s", value: 300.0, start_offset: 1 },
+        ]);
+        assert!(is_better("distance", 5.0, 6.0));
+        assert!(is_better("power", 310.0, 300.0));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::analysis::efforts::resample;
+use crate::strava::parsers::ActivityStreams;
+use chrono::{Days, NaiveDate};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Time constants of the fitness (CTL) and fatigue (ATL) averages, in days
+const CTL_DAYS: f64 = 42.0;
+const ATL_DAYS: f64 = 7.0;
+
+// What the load calculations need from the athlete's thresholds
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Thresholds {
+    pub max_hr: Option<i32>,
+    pub resting_hr: Option<i32>,
+    pub ftp: Option<i32>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ActivityLoad {
+    pub trimp: Option<f32>,
+    pub tss: Option<f32>,
+}
+
+impl ActivityLoad {
+    // Power is the better measure, heart rate is the fallback
+    pub fn load(&self) -> Option<f32> {
+        self.tss.or(self.trimp)
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DailyLoad {
+    pub date: NaiveDate,
+    pub load: f32,
+    // Fitness, fatigue and form
+    pub ctl: f32,
+    pub atl: f32,
+    pub tsb: f32,
+}
+
+pub fn activity_load(streams: &ActivityStreams, thresholds: &Thresholds) -> ActivityLoad {
+    let Some(time) = streams.numbers("time") else {
+        return ActivityLoad::default();
+    };
+    let trimp = match (streams.numbers("heartrate"), thresholds.resting_hr, thresholds.max_hr) {
+        (Some(heartrate), Some(resting), Some(max)) if max > resting => {
+            Some(trimp(&resample(time, heartrate), resting as f32, max as f32))
+        }
+        _ => None,
+    };
+    let tss = match (streams.numbers("watts"), thresholds.ftp) {
+        (Some(watts), Some(ftp)) if ftp > 0 => tss(&resample(time, watts), ftp as f32),
+        _ => None,
+    };
+    ActivityLoad { trimp, tss }
+}
+
+// Banister's TRIMP, one sample per second. Uses the weighting for men (0.64, 1.92) as
+// strava doesn't tell us the athlete's sex reliably.
+pub fn trimp(heartrate: &[f32], resting: f32, max: f32) -> f32 {
+    let total: f64 = heartrate
+        .iter()
+        .filter(|hr| **hr > resting)
+        .map(|hr| {
+            let reserve = ((hr - resting) / (max - resting)).min(1.0) as f64;
+            reserve * 0.64 * (1.92 * reserve).exp()
+        })
+        .sum();
+    (total / 60.0) as f32
+}
+
+// Fourth root of the mean of the 30 second rolling average to the fourth
+pub fn normalized_power(watts: &[f32]) -> Option<f32> {
+    if watts.len() < 30 {
+        return None;
+    }
+    let mut sum: f64 = watts[..30].iter().map(|w| *w as f64).sum();
+    let mut total = (sum / 30.0).powi(4);
+    for end in 30..watts.len() {
+        sum += watts[end] as f64 - watts[end - 30] as f64;
+        total += (sum / 30.0).powi(4);
+    }
+    Some((total / (watts.len() - 29) as f64).powf(0.25) as f32)
+}
+
+// 100 is an hour at FTP
+pub fn tss(watts: &[f32], ftp: f32) -> Option<f32> {
+    let np = normalized_power(watts)?;
+    let intensity = np / ftp;
+    Some(watts.len() as f32 * np * intensity / (ftp * 3600.0) * 100.0)
+}
+
+// Exponentially weighted load, starting from the first activity so the values on `from`
+// include everything before it. TSB is yesterday's CTL minus yesterday's ATL.
+pub fn daily_series(loads: &[(NaiveDate, f32)], from: NaiveDate, to: NaiveDate) -> Vec<DailyLoad> {
+    let mut per_day: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+    for (date, load) in loads {
+        *per_day.entry(*date).or_default() += *load as f64;
+    }
+    let start = per_day.keys().next().copied().unwrap_or(from).min(from);
+
+    let mut series = Vec::new();
+    let (mut ctl, mut atl) = (0.0, 0.0);
+    let mut date = start;
+    while date <= to {
+        let load = per_day.get(&date).copied().unwrap_or_default();
+        let tsb = ctl - atl;
+        ctl += (load - ctl) / CTL_DAYS;
+        atl += (load - atl) / ATL_DAYS;
+        if date >= from {
+            series.push(DailyLoad { date, load: load as f32, ctl: ctl as f32, atl: atl as f32, tsb: tsb as f32 });
+        }
+        date = date + Days::new(1);
+    }
+    series
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tss() {
+        // An hour steady at FTP is 100
+        let watts = vec![250.0; 3600];
+        assert_eq!(normalized_power(&watts), Some(250.0));
+        assert!((tss(&watts, 250.0).unwrap() - 100.0).abs() < 0.01);
+
+        // Surges count for more than their average
+        let surges: Vec<f32> = (0..3600).map(|s| if (s / 60) % 2 == 0 { 350.0 } else { 150.0 }).collect();
+        assert!(normalized_power(&surges).unwrap() > 250.0);
+        assert_eq!(normalized_power(&watts[..29]), None);
+    }
+
+    #[test]
+    fn test_trimp() {
+        // An hour at max heart rate and an hour below resting
+        assert!((trimp(&vec![190.0; 3600], 50.0, 190.0) - 60.0 * 0.64 * 1.92f32.exp()).abs() < 0.01);
+        assert_eq!(trimp(&vec![45.0; 3600], 50.0, 190.0), 0.0);
+    }
+
+    #[test]
+    fn test_activity_load() {
+        let streams = r#"[{"type": "time", "data": [0, 1, 2], "series_type": "time", "original_size": 3, "resolution": "high"},
+            {"type": "heartrate", "data": [120, 130, 140], "series_type": "time", "original_size": 3, "resolution": "high"}]"#;
+        let streams = ActivityStreams::parse(streams.as_bytes()).unwrap();
+
+        let load = activity_load(&streams, &Thresholds { max_hr: Some(190), resting_hr: Some(50), ftp: Some(250) });
+        assert!(load.trimp.unwrap() > 0.0);
+        assert_eq!(load.tss, None);
+        assert_eq!(load.load(), load.trimp);
+        assert_eq!(activity_load(&streams, &Thresholds::default()), ActivityLoad::default());
+    }
+
+    #[test]
+    fn test_daily_series() {
+        let day = |d: &str| d.parse::<NaiveDate>().unwrap();
+        let loads = vec![(day("2024-01-01"), 100.0), (day("2024-01-01"), 20.0), (day("2024-01-03"), 70.0)];
+
+        let series = daily_series(&loads, day("2024-01-02"), day("2024-01-04"));
+        assert_eq!(series.iter().map(|d| d.date).collect::<Vec<_>>(), vec![day("2024-01-02"), day("2024-01-03"), day("2024-01-04")]);
+
+        // The first day before the range still counts
+        let ctl_day_1 = 120.0 / 42.0;
+        let atl_day_1 = 120.0 / 7.0;
+        assert!((series[0].ctl - ctl_day_1 * (41.0 / 42.0)).abs() < 0.001);
+        assert!((series[0].tsb - (ctl_day_1 - atl_day_1)).abs() < 0.001);
+        assert_eq!(series[1].load, 70.0);
+        assert!(series[2].atl < series[1].atl);
+
+        assert_eq!(daily_series(&[], day("2024-01-01"), day("2024-01-07")).len(), 7);
+    }
+}
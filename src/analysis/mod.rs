@@ -0,0 +1,257 @@
+pub mod efforts;
+pub mod load;
+pub mod power;
+pub mod zones;
+
+use crate::ApiError;
+use crate::heatmap;
+use crate::polyline;
+use crate::models::activity::{activities_to_analyze, get_activity};
+use crate::models::blob::find_blob_ref;
+use crate::models::analysis::{AnalysisRows, save_analysis};
+use crate::models::effort::{BestEffortRow, NewBestEffortRow};
+use crate::models::heatmap::NewActivityBoundsRow;
+use crate::models::power_curve::NewPowerCurveRow;
+use crate::models::training_load::{NewActivityLoadRow, get_thresholds_at};
+use crate::models::zones::{NewZoneTimeRow, get_zones};
+use crate::storage::blob_store::BlobStore;
+use crate::strava::parsers::ActivityStreams;
+use crate::sync::connection;
+use axum::http::StatusCode;
+use chrono::{DateTime, Datelike, Utc};
+use deadpool_diesel::postgres::Pool;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Compute everything derived from the streams of one activity. Returns false when the
+// activity has no streams stored yet or they can't be read.
+pub async fn analyze_activity(pool: &Pool, blob_store: &BlobStore, activity_id: i64) -> Result<bool, ApiError> {
+    let activity = get_activity(connection(pool).await?, activity_id).await?.ok_or(ApiError {
+        status_code: StatusCode::NOT_FOUND,
+        message: "Activity not found".to_string(),
+    })?;
+    let owner = format!("activity:{}:streams", activity_id);
+    let Some(hash) = find_blob_ref(connection(pool).await?, owner).await? else {
+        return Ok(false);
+    };
+    // A blob missing from the store is left for the next sync of the streams, it must
+    // not stop the analysis of the other activities
+    let Ok(bytes) = blob_store.get(&hash) else {
+        eprintln!("Could not read blob {} of activity {}", hash, activity_id);
+        return Ok(false);
+    };
+
+    // Streams that don't parse have nothing to give, the activity is still marked as
+    // analyzed so it isn't picked up on every sync
+    let thresholds = get_thresholds_at(connection(pool).await?, activity.athlete_id, activity.start_date.date_naive())
+        .await?
+        .map(|row| load::Thresholds { max_hr: row.max_hr, resting_hr: row.resting_hr, ftp: row.ftp })
+        .unwrap_or_default();
+
+    let zone_rows = get_zones(connection(pool).await?, activity.athlete_id).await?;
+    let heart_rate_zones = zones::resolve_zones(&zone_rows, "heartrate", &thresholds).map(|(_, zones)| zones);
+    let power_zones = zones::resolve_zones(&zone_rows, "power", &thresholds).map(|(_, zones)| zones);
+
+    let (efforts, curve, activity_load, zone_times, track) = match ActivityStreams::parse(&bytes) {
+        Ok(streams) => (
+            efforts::best_efforts(&streams, activity.sport_type.as_deref()),
+            power::power_curve(&streams),
+            load::activity_load(&streams, &thresholds),
+            zones::activity_zone_times(&streams, heart_rate_zones.as_deref(), power_zones.as_deref()),
+            streams.latlng().filter(|track| !track.is_empty()).map(<[[f64; 2]]>::to_vec),
+        ),
+        Err(_) => (Vec::new(), Vec::new(), load::ActivityLoad::default(), Vec::new(), None),
+    };
+    let best_efforts = efforts
+        .into_iter()
+        .map(|effort| NewBestEffortRow {
+            activity_id,
+            kind: effort.kind.to_string(),
+            name: effort.name.to_string(),
+            athlete_id: activity.athlete_id,
+            sport_type: activity.sport_type.clone(),
+            value: effort.value,
+            start_offset: effort.start_offset,
+            start_date: activity.start_date,
+            created_at: Utc::now().naive_utc(),
+        })
+        .collect();
+    let power_curve = curve
+        .into_iter()
+        .map(|point| NewPowerCurveRow {
+            activity_id,
+            duration: point.duration,
+            watts: point.watts,
+            athlete_id: activity.athlete_id,
+            start_date: activity.start_date,
+        })
+        .collect();
+    let load = activity_load.load().map(|value| NewActivityLoadRow {
+        activity_id,
+        athlete_id: activity.athlete_id,
+        start_date: activity.start_date,
+        trimp: activity_load.trimp,
+        tss: activity_load.tss,
+        load: value,
+    });
+    let zone_times = zone_times
+        .into_iter()
+        .map(|zone_time| NewZoneTimeRow {
+            activity_id,
+            kind: zone_time.kind.to_string(),
+            zone: zone_time.zone,
+            seconds: zone_time.seconds,
+            athlete_id: activity.athlete_id,
+            start_date: activity.start_date,
+        })
+        .collect();
+    let bounds = track.as_deref().and_then(heatmap::track_bounds).map(|bounds| NewActivityBoundsRow {
+        activity_id,
+        athlete_id: activity.athlete_id,
+        sport_type: activity.sport_type.clone(),
+        start_date: activity.start_date,
+        min_lat: bounds.min_lat,
+        min_lng: bounds.min_lng,
+        max_lat: bounds.max_lat,
+        max_lng: bounds.max_lng,
+    });
+    let track_polyline = track.as_deref().map(polyline::encode);
+    let rows = AnalysisRows { best_efforts, power_curve, load, zone_times, bounds, track_polyline };
+    save_analysis(connection(pool).await?, activity_id, rows).await?;
+    Ok(true)
+}
+
+// Analyze the activities with streams that weren't analyzed yet, returns how many
+pub async fn analyze_pending(pool: &Pool, blob_store: &BlobStore) -> Result<usize, ApiError> {
+    let mut analyzed = 0;
+    for activity_id in activities_to_analyze(connection(pool).await?).await? {
+        match analyze_activity(pool, blob_store, activity_id).await {
+            Ok(true) => analyzed += 1,
+            Ok(false) => {}
+            // Deleted since the list was loaded
+            Err(e) if e.status_code == StatusCode::NOT_FOUND => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(analyzed)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PersonalRecord {
+    pub kind: String,
+    pub name: String,
+    pub value: f32,
+    pub activity_id: i64,
+    pub start_date: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RecordTable {
+    pub all_time: Vec<PersonalRecord>,
+    pub yearly: BTreeMap<i32, Vec<PersonalRecord>>,
+}
+
+// Distances shortest first, then the power and heart rate durations shortest first
+fn record_order(record: &PersonalRecord) -> (usize, usize) {
+    let kind = ["distance", "power", "heartrate"].iter().position(|k| *k == record.kind);
+    let name = match record.kind.as_str() {
+        "distance" => efforts::DISTANCES.iter().position(|(name, _)| *name == record.name),
+        _ => efforts::DURATIONS.iter().position(|(name, _)| *name == record.name),
+    };
+    (kind.unwrap_or(usize::MAX), name.unwrap_or(usize::MAX))
+}
+
+fn best_of(records: &mut Vec<PersonalRecord>, effort: &BestEffortRow) {
+    let candidate = PersonalRecord {
+        kind: effort.kind.clone(),
+        name: effort.name.clone(),
+        value: effort.value,
+        activity_id: effort.activity_id,
+        start_date: effort.start_date,
+    };
+    match records.iter_mut().find(|r| r.kind == effort.kind && r.name == effort.name) {
+        // Ties keep the older effort, it got there first
+        Some(record) if efforts::is_better(&effort.kind, effort.value, record.value) => *record = candidate,
+        Some(_) => (),
+        None => records.push(candidate),
+    }
+}
+
+// The best effort for every distance and duration, all time and per year
+pub fn personal_records(efforts: &[BestEffortRow]) -> RecordTable {
+    let mut table = RecordTable::default();
+    let mut efforts = efforts.to_vec();
+    efforts.sort_by_key(|effort| effort.start_date);
+    for effort in &efforts {
+        best_of(&mut table.all_time, effort);
+        best_of(table.yearly.entry(effort.start_date.year()).or_default(), effort);
+    }
+    table.all_time.sort_by_key(record_order);
+    for records in table.yearly.values_mut() {
+        records.sort_by_key(record_order);
+    }
+    table
+}
+
+// One table per sport, a ride's 5min power doesn't compete with a run's
+pub fn records_by_sport(efforts: &[BestEffortRow]) -> BTreeMap<String, RecordTable> {
+    let mut by_sport: BTreeMap<String, Vec<BestEffortRow>> = BTreeMap::new();
+    for effort in efforts {
+        let sport = effort.sport_type.clone().unwrap_or_else(|| "Unknown".to_string());
+        by_sport.entry(sport).or_default().push(effort.clone());
+    }
+    by_sport.into_iter().map(|(sport, efforts)| (sport, personal_records(&efforts))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effort(activity_id: i64, start_date: &str, kind: &str, name: &str, value: f32) -> BestEffortRow {
+        let start_date = start_date.parse::<DateTime<Utc>>().unwrap();
+        BestEffortRow {
+            activity_id,
+            kind: kind.to_string(),
+            name: name.to_string(),
+            athlete_id: 28853829,
+            sport_type: Some("Run".to_string()),
+            value,
+            start_offset: 0,
+            start_date,
+            created_at: start_date.naive_utc(),
+        }
+    }
+
+    #[test]
+    fn test_personal_records() {
+        let efforts = vec![
+            effort(3, "2024-05-01T08:00:00Z", "distance", "5k", 1290.0),
+            effort(1, "2023-04-01T08:00:00Z", "distance", "5k", 1320.0),
+            effort(1, "2023-04-01T08:00:00Z", "distance", "1k", 240.0),
+            effort(2, "2023-09-01T08:00:00Z", "distance", "1k", 240.0),
+            effort(2, "2023-09-01T08:00:00Z", "power", "5min", 310.0),
+            effort(3, "2024-05-01T08:00:00Z", "power", "5min", 290.0),
+        ];
+        let table = personal_records(&efforts);
+
+        let all_time: Vec<_> = table.all_time.iter().map(|r| (r.name.as_str(), r.value, r.activity_id)).collect();
+        assert_eq!(all_time, vec![("1k", 240.0, 1), ("5k", 1290.0, 3), ("5min", 310.0, 2)]);
+
+        assert_eq!(table.yearly.keys().copied().collect::<Vec<_>>(), vec![2023, 2024]);
+        let in_2024: Vec<_> = table.yearly[&2024].iter().map(|r| (r.name.as_str(), r.value)).collect();
+        assert_eq!(in_2024, vec![("5k", 1290.0), ("5min", 290.0)]);
+        assert!(personal_records(&[]).all_time.is_empty());
+    }
+
+    #[test]
+    fn test_records_by_sport() {
+        let mut ride = effort(4, "2024-06-01T08:00:00Z", "power", "5min", 350.0);
+        ride.sport_type = Some("Ride".to_string());
+        let efforts = vec![effort(2, "2023-09-01T08:00:00Z", "power", "5min", 310.0), ride];
+        let tables = records_by_sport(&efforts);
+
+        assert_eq!(tables.keys().map(String::as_str).collect::<Vec<_>>(), vec!["Ride", "Run"]);
+        assert_eq!((tables["Run"].all_time[0].value, tables["Run"].all_time[0].activity_id), (310.0, 2));
+        assert_eq!((tables["Ride"].all_time[0].value, tables["Ride"].all_time[0].activity_id), (350.0, 4));
+    }
+}
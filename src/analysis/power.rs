@@ -0,0 +1,104 @@
+use crate::analysis::efforts::{best_average, resample};
+use crate::strava::parsers::ActivityStreams;
+use serde::Serialize;
+
+// Seconds, dense at the short end where the curve changes fast
+pub const CURVE_DURATIONS: [usize; 26] = [
+    1, 2, 3, 5, 10, 15, 20, 30, 45, 60, 90, 120, 180, 240, 300, 420, 600, 720, 900, 1200, 1800, 2700, 3600,
+    5400, 7200, 10800,
+];
+
+// The part of the curve the critical power model holds for, 3 to 20 minutes
+const CP_MIN_DURATION: i32 = 180;
+const CP_MAX_DURATION: i32 = 1200;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CurvePoint {
+    pub duration: i32,
+    pub watts: f32,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct FtpEstimate {
+    // 95% of the best 20 minutes
+    pub twenty_minute: Option<f32>,
+    // Two parameter model, work = critical_power * t + w_prime
+    pub critical_power: Option<f32>,
+    pub w_prime: Option<f32>,
+}
+
+// Mean maximal power for every duration the activity is long enough for
+pub fn power_curve(streams: &ActivityStreams) -> Vec<CurvePoint> {
+    let (Some(time), Some(watts)) = (streams.numbers("time"), streams.numbers("watts")) else {
+        return Vec::new();
+    };
+    let per_second = resample(time, watts);
+    CURVE_DURATIONS
+        .iter()
+        .filter_map(|seconds| {
+            best_average(&per_second, *seconds).map(|(watts, _)| CurvePoint { duration: *seconds as i32, watts })
+        })
+        .collect()
+}
+
+pub fn estimate_ftp(curve: &[CurvePoint]) -> FtpEstimate {
+    let twenty_minute = curve.iter().find(|p| p.duration == 1200).map(|p| p.watts * 0.95);
+
+    // Least squares of work against time, the slope is CP and the intercept W'
+    let points: Vec<(f64, f64)> = curve
+        .iter()
+        .filter(|p| (CP_MIN_DURATION..=CP_MAX_DURATION).contains(&p.duration))
+        .map(|p| (p.duration as f64, p.watts as f64 * p.duration as f64))
+        .collect();
+    let n = points.len() as f64;
+    let mean_t = points.iter().map(|(t, _)| t).sum::<f64>() / n;
+    let mean_w = points.iter().map(|(_, w)| w).sum::<f64>() / n;
+    let covariance: f64 = points.iter().map(|(t, w)| (t - mean_t) * (w - mean_w)).sum();
+    let variance: f64 = points.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+
+    let (critical_power, w_prime) = match points.len() >= 3 && variance > 0.0 {
+        true => {
+            let slope = covariance / variance;
+            (Some(slope as f32), Some((mean_w - slope * mean_t) as f32))
+        }
+        false => (None, None),
+    };
+    FtpEstimate { twenty_minute, critical_power, w_prime }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_power_curve() {
+        let streams = r#"[{"type": "time", "data": [0, 1, 2, 3, 4], "series_type": "time", "original_size": 5, "resolution": "high"},
+            {"type": "watts", "data": [200, 600, 400, 200, 100], "series_type": "time", "original_size": 5, "resolution": "high"}]"#;
+        let curve = power_curve(&ActivityStreams::parse(streams.as_bytes()).unwrap());
+        assert_eq!(curve, vec![
+            CurvePoint { duration: 1, watts: 600.0 },
+            CurvePoint { duration: 2, watts: 500.0 },
+            CurvePoint { duration: 3, watts: 400.0 },
+            CurvePoint { duration: 5, watts: 300.0 },
+        ]);
+
+        let streams = r#"[{"type": "time", "data": [0, 1], "series_type": "time", "original_size": 2, "resolution": "high"}]"#;
+        assert!(power_curve(&ActivityStreams::parse(streams.as_bytes()).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_estimate_ftp() {
+        // A rider that follows the model exactly with CP 250W and W' 20kJ
+        let curve: Vec<CurvePoint> = CURVE_DURATIONS
+            .iter()
+            .map(|d| CurvePoint { duration: *d as i32, watts: 250.0 + 20000.0 / *d as f32 })
+            .collect();
+        let ftp = estimate_ftp(&curve);
+        assert!((ftp.critical_power.unwrap() - 250.0).abs() < 0.1);
+        assert!((ftp.w_prime.unwrap() - 20000.0).abs() < 10.0);
+        assert!((ftp.twenty_minute.unwrap() - (250.0 + 20000.0 / 1200.0) * 0.95).abs() < 0.01);
+
+        // Short rides don't have enough of the curve for either estimate
+        assert_eq!(estimate_ftp(&curve[..10]), FtpEstimate::default());
+    }
+}
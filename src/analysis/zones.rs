@@ -0,0 +1,206 @@
+use crate::analysis::efforts::resample;
+use crate::analysis::load::Thresholds;
+use crate::models::zones::ZoneRow;
+use crate::stats::week_key;
+use crate::strava::parsers::{ActivityStreams, ZoneBoundary};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Upper bounds of the default zones, as a share of max heart rate and of FTP (Coggan)
+const HEART_RATE_ZONES: [f32; 4] = [0.6, 0.7, 0.8, 0.9];
+const POWER_ZONES: [f32; 6] = [0.55, 0.75, 0.9, 1.05, 1.2, 1.5];
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ZoneTime {
+    // "heartrate" or "power"
+    pub kind: &'static str,
+    // Zones count from 1 like on strava
+    pub zone: i32,
+    pub seconds: i32,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct WeeklyZones {
+    pub week: String,
+    // Seconds per zone, zone 1 first
+    pub seconds: Vec<i64>,
+}
+
+fn from_bounds(threshold: i32, shares: &[f32]) -> Vec<ZoneBoundary> {
+    let mut min = 0;
+    let mut zones = Vec::new();
+    for share in shares {
+        let max = (threshold as f32 * share).round() as i32;
+        zones.push(ZoneBoundary { min, max });
+        min = max;
+    }
+    zones.push(ZoneBoundary { min, max: -1 });
+    zones
+}
+
+// Zones for when the athlete hasn't set any and strava didn't give us theirs
+pub fn default_zones(kind: &str, thresholds: &Thresholds) -> Option<Vec<ZoneBoundary>> {
+    match kind {
+        "heartrate" => thresholds.max_hr.map(|max_hr| from_bounds(max_hr, &HEART_RATE_ZONES)),
+        "power" => thresholds.ftp.map(|ftp| from_bounds(ftp, &POWER_ZONES)),
+        _ => None,
+    }
+}
+
+// The athlete's own zones win over strava's, and those over the defaults. Returns the
+// source with the zones.
+pub fn resolve_zones(rows: &[ZoneRow], kind: &str, thresholds: &Thresholds) -> Option<(&'static str, Vec<ZoneBoundary>)> {
+    for source in ["custom", "strava"] {
+        let zones: Vec<ZoneBoundary> = rows
+            .iter()
+            .filter(|row| row.kind == kind && row.source == source)
+            .map(|row| ZoneBoundary { min: row.min, max: row.max })
+            .collect();
+        if !zones.is_empty() {
+            return Some((source, zones));
+        }
+    }
+    default_zones(kind, thresholds).map(|zones| ("default", zones))
+}
+
+// Ascending and without gaps, only the last zone may be open ended
+pub fn validate_zones(zones: &[ZoneBoundary]) -> Result<(), &'static str> {
+    if zones.is_empty() {
+        return Err("At least one zone is needed");
+    }
+    for (i, zone) in zones.iter().enumerate() {
+        let last = i + 1 == zones.len();
+        if zone.max == -1 && !last {
+            return Err("Only the last zone can be open ended");
+        }
+        if zone.max != -1 && zone.max <= zone.min {
+            return Err("Every zone must end above where it starts");
+        }
+        if !last && zones[i + 1].min != zone.max {
+            return Err("Every zone must start where the previous one ends");
+        }
+    }
+    Ok(())
+}
+
+// Seconds in each zone. Seconds without a reading (zero, e.g. a pause) aren't counted.
+pub fn time_in_zones(per_second: &[f32], zones: &[ZoneBoundary]) -> Vec<i32> {
+    let mut seconds = vec![0; zones.len()];
+    for value in per_second.iter().filter(|v| **v > 0.0) {
+        let zone = zones
+            .iter()
+            .position(|z| *value < z.max as f32 || z.max == -1)
+            .unwrap_or(zones.len() - 1);
+        seconds[zone] += 1;
+    }
+    seconds
+}
+
+pub fn activity_zone_times(
+    streams: &ActivityStreams,
+    heart_rate_zones: Option<&[ZoneBoundary]>,
+    power_zones: Option<&[ZoneBoundary]>,
+) -> Vec<ZoneTime> {
+    let Some(time) = streams.numbers("time") else {
+        return Vec::new();
+    };
+    let mut zone_times = Vec::new();
+    for (kind, stream_type, zones) in [("heartrate", "heartrate", heart_rate_zones), ("power", "watts", power_zones)] {
+        let (Some(values), Some(zones)) = (streams.numbers(stream_type), zones) else {
+            continue;
+        };
+        if zones.is_empty() {
+            continue;
+        }
+        for (i, seconds) in time_in_zones(&resample(time, values), zones).into_iter().enumerate() {
+            zone_times.push(ZoneTime { kind, zone: i as i32 + 1, seconds });
+        }
+    }
+    zone_times
+}
+
+// Sum (start, zone, seconds) rows per ISO week
+pub fn weekly(rows: &[(DateTime<Utc>, i32, i32)]) -> Vec<WeeklyZones> {
+    let mut weeks: BTreeMap<String, Vec<i64>> = BTreeMap::new();
+    for (start_date, zone, seconds) in rows {
+        let zones = weeks.entry(week_key(*start_date)).or_default();
+        let index = (*zone - 1).max(0) as usize;
+        if zones.len() <= index {
+            zones.resize(index + 1, 0);
+        }
+        zones[index] += *seconds as i64;
+    }
+    weeks.into_iter().map(|(week, seconds)| WeeklyZones { week, seconds }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_zones() {
+        let thresholds = Thresholds { max_hr: Some(200), resting_hr: None, ftp: Some(250) };
+        let heart_rate = default_zones("heartrate", &thresholds).unwrap();
+        assert_eq!(heart_rate.len(), 5);
+        assert_eq!(heart_rate[0], ZoneBoundary { min: 0, max: 120 });
+        assert_eq!(heart_rate[4], ZoneBoundary { min: 180, max: -1 });
+        assert!(validate_zones(&heart_rate).is_ok());
+
+        let power = default_zones("power", &thresholds).unwrap();
+        assert_eq!(power.len(), 7);
+        assert_eq!(power[3], ZoneBoundary { min: 225, max: 263 });
+        assert!(default_zones("power", &Thresholds::default()).is_none());
+    }
+
+    #[test]
+    fn test_resolve_zones() {
+        let row = |source: &str, zone, min, max| ZoneRow {
+            athlete_id: 28853829,
+            kind: "heartrate".to_string(),
+            source: source.to_string(),
+            zone,
+            min,
+            max,
+        };
+        let thresholds = Thresholds { max_hr: Some(200), resting_hr: None, ftp: None };
+
+        let rows = vec![row("strava", 1, 0, 150), row("strava", 2, 150, -1), row("custom", 1, 0, -1)];
+        assert_eq!(resolve_zones(&rows, "heartrate", &thresholds).unwrap(), ("custom", vec![ZoneBoundary { min: 0, max: -1 }]));
+        assert_eq!(resolve_zones(&rows[..2], "heartrate", &thresholds).unwrap().0, "strava");
+        assert_eq!(resolve_zones(&[], "heartrate", &thresholds).unwrap().0, "default");
+        assert!(resolve_zones(&[], "power", &thresholds).is_none());
+    }
+
+    #[test]
+    fn test_validate_zones() {
+        let zone = |min, max| ZoneBoundary { min, max };
+        assert!(validate_zones(&[zone(0, 100), zone(100, -1)]).is_ok());
+        assert!(validate_zones(&[]).is_err());
+        assert!(validate_zones(&[zone(0, -1), zone(100, 150)]).is_err());
+        assert!(validate_zones(&[zone(0, 100), zone(110, -1)]).is_err());
+        assert!(validate_zones(&[zone(0, 100), zone(100, 90)]).is_err());
+    }
+
+    #[test]
+    fn test_time_in_zones() {
+        let zones = [ZoneBoundary { min: 0, max: 120 }, ZoneBoundary { min: 120, max: 160 }, ZoneBoundary { min: 160, max: -1 }];
+        let per_second = [0.0, 110.0, 119.0, 120.0, 150.0, 159.9, 160.0, 190.0];
+        assert_eq!(time_in_zones(&per_second, &zones), vec![2, 3, 2]);
+    }
+
+    #[test]
+    fn test_weekly() {
+        let date = |d: &str| d.parse::<DateTime<Utc>>().unwrap();
+        let rows = vec![
+            (date("2024-01-01T08:00:00Z"), 1, 600),
+            (date("2024-01-03T08:00:00Z"), 1, 300),
+            (date("2024-01-03T08:00:00Z"), 3, 120),
+            (date("2024-01-08T08:00:00Z"), 2, 60),
+        ];
+        assert_eq!(weekly(&rows), vec![
+            WeeklyZones { week: "2024-W01".to_string(), seconds: vec![900, 0, 120] },
+            WeeklyZones { week: "2024-W02".to_string(), seconds: vec![0, 60] },
+        ]);
+    }
+}
@@ -0,0 +1,461 @@
+use crate::analysis::analyze_pending;
+use crate::crypto::TokenCipher;
+use crate::db_connection::establish_connection;
+use crate::kml;
+use crate::export::{
+    CsvWriter, ParquetWriter, STREAM_ROW_GROUP_SIZE, Table, activity_records, activity_table, stream_table,
+};
+use crate::models::activity::{
+    ActivityFilter, ActivityRow, ActivitySort, Cursor, SortOrder, count_activities, get_activities, latest_start_date,
+    query_activities, reset_analysis,
+};
+use crate::models::blob::find_blob_ref;
+use crate::models::social::{get_comments, get_kudos, social_counts};
+use crate::settings::Settings;
+use crate::storage::archive_file;
+use crate::storage::blob_store::BlobStore;
+use crate::storage::s3::S3Store;
+use crate::strava::client::{LOGIN_STATE, StravaClient};
+use crate::strava::login::code_from_redirect;
+use crate::strava::parsers::ActivityStreams;
+use crate::sync::{SyncReport, collect_garbage, sync_activities};
+use axum::Router;
+use axum::extract::Query;
+use axum::routing::get;
+use clap::{Parser, Subcommand, ValueEnum};
+use deadpool_diesel::postgres::Pool;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use url::Url;
+
+// Activities read from the db at a time by the tabular exports
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+#[derive(Parser)]
+#[command(name = "strava-backup", about = "Back up your strava data")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Log in with strava, a local listener catches the redirect
+    Login {
+        /// Don't listen for the redirect, paste the url the browser ended on instead
+        #[arg(long)]
+        paste: bool,
+        /// Overrides strava.redirect_uri, e.g. a public callback
+        #[arg(long)]
+        redirect_uri: Option<String>,
+    },
+    /// Fetch the activities newer than the latest stored one
+    Sync {
+        /// Only fetch the activity summaries
+        #[arg(long)]
+        no_streams: bool,
+    },
+    /// Fetch the whole history, can be stopped and started again
+    Backfill {
+        /// Only fetch the activity summaries
+        #[arg(long)]
+        no_streams: bool,
+    },
+    /// Write the stored activities to a file
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Jsonl)]
+        format: ExportFormat,
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Compute best efforts, power curves, training load and zones from the stored streams
+    Analyze {
+        /// Analyze every activity again, not only the new ones
+        #[arg(long)]
+        all: bool,
+    },
+    /// Re-hash every blob and report the corrupted ones
+    Verify,
+    /// Delete the blobs no activity, photo or route references anymore
+    Gc,
+    /// Run the http server (the default)
+    Serve,
+    /// Show login and backup status
+    Status,
+    /// Re-encrypt the token file with the active key
+    ReencryptTokens,
+}
+
+#[derive(Clone, ValueEnum)]
+pub enum ExportFormat {
+    /// One json activity per line
+    Jsonl,
+    /// Google Earth, a folder per year with a line per activity
+    Kml,
+    /// Zipped kml
+    Kmz,
+    /// One row per activity
+    Csv,
+    /// One row per activity, typed columns
+    Parquet,
+    /// One row per stream sample of every activity
+    StreamsParquet,
+}
+
+impl ExportFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Jsonl => "application/x-ndjson",
+            ExportFormat::Kml => "application/vnd.google-earth.kml+xml",
+            ExportFormat::Kmz => "application/vnd.google-earth.kmz",
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Parquet | ExportFormat::StreamsParquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+pub async fn run(command: Command, settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let token_cipher = Arc::new(TokenCipher::from_settings(&settings.tokens)?);
+    let sc = StravaClient::init(&settings.strava, token_cipher.clone());
+
+    match command {
+        Command::Login { paste, redirect_uri } => {
+            let mut strava = settings.strava.clone();
+            if let Some(redirect_uri) = redirect_uri {
+                Url::parse(&redirect_uri)?;
+                strava.redirect_uri = redirect_uri;
+            }
+            let sc = StravaClient::init(&strava, token_cipher);
+            match paste {
+                true => login_with_paste(&sc).await,
+                false => login(&strava.redirect_uri, &sc).await,
+            }
+        }
+        Command::Sync { no_streams } => {
+            let conn = establish_connection(&settings.database);
+            let after = latest_start_date(conn.get().await?).await?.map(|date| date.timestamp());
+            let report = sync(settings, &sc, after, !no_streams, false).await?;
+            println!(
+                "Synced {} activities, {} details, {} streams, {} photos, {} routes and {} club activities, refreshed the comments and kudos of {}, analyzed {}",
+                report.activities,
+                report.details,
+                report.streams,
+                report.photos,
+                report.routes,
+                report.club_activities,
+                report.social,
+                report.analyzed
+            );
+            Ok(())
+        }
+        Command::Backfill { no_streams } => {
+            let report = sync(settings, &sc, None, !no_streams, true).await?;
+            println!(
+                "Backfilled {} activities, {} details, {} streams, {} photos, {} routes and {} club activities, refreshed the comments and kudos of {}, analyzed {}",
+                report.activities,
+                report.details,
+                report.streams,
+                report.photos,
+                report.routes,
+                report.club_activities,
+                report.social,
+                report.analyzed
+            );
+            Ok(())
+        }
+        Command::Export { format, output } => export(settings, format, output).await,
+        Command::Analyze { all } => {
+            let pool = establish_connection(&settings.database);
+            if all {
+                reset_analysis(pool.get().await?).await?;
+            }
+            let analyzed = analyze_pending(&pool, &BlobStore::from_settings(&settings.storage)).await?;
+            println!("Analyzed {} activities", analyzed);
+            Ok(())
+        }
+        Command::Verify => verify(settings),
+        Command::Gc => {
+            let pool = establish_connection(&settings.database);
+            let removed = collect_garbage(&BlobStore::from_settings(&settings.storage), &pool).await?;
+            println!("Deleted {} unreferenced blobs", removed);
+            Ok(())
+        }
+        Command::Status => status(settings, &sc).await,
+        Command::ReencryptTokens => {
+            match sc.reencrypt_token_file()? {
+                true => println!("Re-encrypted the token file with the active key"),
+                false => println!("The token file already uses the active key"),
+            }
+            Ok(())
+        }
+        Command::Serve => unreachable!("serve is handled by main"),
+    }
+}
+
+#[derive(Deserialize)]
+struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+// Listen on the redirect uri for a single request, strava sends the code there once the
+// user accepts in the browser
+async fn login(redirect_uri: &str, sc: &StravaClient) -> Result<(), Box<dyn Error>> {
+    let redirect_uri = Url::parse(redirect_uri)?;
+    let host = redirect_uri.host_str().ok_or("The redirect uri has no host")?.to_string();
+    let port = redirect_uri.port_or_known_default().ok_or("The redirect uri has no port")?;
+    let listener = tokio::net::TcpListener::bind((host.as_str(), port)).await?;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<CallbackParams>(1);
+    let app = Router::new().route(
+        redirect_uri.path(),
+        get(move |Query(params): Query<CallbackParams>| async move {
+            let _ = tx.send(params).await;
+            "Done, you can close this window and go back to the terminal"
+        }),
+    );
+    let server = tokio::spawn(async move { axum::serve(listener, app).await });
+
+    println!("Open this url in your browser to log in:\n\n  {}\n", sc.login_link().await.url);
+    let params = rx.recv().await.ok_or("The login listener stopped")?;
+    server.abort();
+
+    if let Some(error) = params.error {
+        return Err(format!("Strava refused the login: {}", error).into());
+    }
+    if params.state.as_deref() != Some(LOGIN_STATE) {
+        return Err("The login state doesn't match, try again".into());
+    }
+    let code = params.code.ok_or("Strava didn't send a code")?;
+    sc.code_exchange(&code).await?;
+
+    println!("Logged in");
+    Ok(())
+}
+
+// For boxes without a browser: the user opens the link anywhere, and pastes back the url
+// the browser was redirected to (it doesn't matter if that page failed to load)
+async fn login_with_paste(sc: &StravaClient) -> Result<(), Box<dyn Error>> {
+    println!("Open this url in any browser to log in:\n\n  {}\n", sc.login_link().await.url);
+    println!("Once you accept, paste the url of the page you land on (or just the code in it):");
+
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    let code = code_from_redirect(&input)?;
+    sc.code_exchange(&code).await?;
+
+    println!("Logged in");
+    Ok(())
+}
+
+async fn sync(
+    settings: &Settings,
+    sc: &StravaClient,
+    after: Option<i64>,
+    details: bool,
+    skip_synced: bool,
+) -> Result<SyncReport, Box<dyn Error>> {
+    let pool = establish_connection(&settings.database);
+    let blob_store = BlobStore::from_settings(&settings.storage);
+    let object_store = settings.s3.as_ref().map(S3Store::from_settings);
+
+    let report = sync_activities(
+        sc,
+        &pool,
+        &blob_store,
+        object_store.as_ref(),
+        after,
+        details,
+        skip_synced,
+    )
+    .await?;
+    Ok(report)
+}
+
+async fn export(settings: &Settings, format: ExportFormat, output: PathBuf) -> Result<(), Box<dyn Error>> {
+    let pool = establish_connection(&settings.database);
+    let mut writer = BufWriter::new(File::create(&output)?);
+    let count = match format {
+        ExportFormat::Jsonl | ExportFormat::Kml | ExportFormat::Kmz => {
+            let activities = get_activities(pool.get().await?).await?;
+            match format {
+                ExportFormat::Kml => writer.write_all(kml::document(&activities).as_bytes())?,
+                ExportFormat::Kmz => writer.write_all(&kml::kmz(&kml::document(&activities))?)?,
+                _ => {
+                    let ids: Vec<i64> = activities.iter().map(|activity| activity.id).collect();
+                    let comments = get_comments(pool.get().await?, ids.clone()).await?;
+                    let kudos = get_kudos(pool.get().await?, ids).await?;
+                    for record in activity_records(&activities, &comments, &kudos) {
+                        serde_json::to_writer(&mut writer, &record)?;
+                        writer.write_all(b"\n")?;
+                    }
+                }
+            }
+            writer.flush()?;
+            activities.len()
+        }
+        ExportFormat::Csv => {
+            let social = social_counts(pool.get().await?).await?;
+            let mut csv = CsvWriter::new(writer);
+            let count = export_pages(&pool, |page| csv.write(&activity_table(page, &social))).await?;
+            csv.finish()?;
+            count
+        }
+        ExportFormat::Parquet => {
+            let social = social_counts(pool.get().await?).await?;
+            let mut parquet = ParquetWriter::activities(writer)?;
+            let count = export_pages(&pool, |page| parquet.write(activity_table(page, &social))).await?;
+            parquet.finish()?;
+            count
+        }
+        ExportFormat::StreamsParquet => export_streams(settings, &pool, writer).await?,
+    };
+
+    println!("Exported {} activities to {}", count, output.display());
+    // Exports go off-site too when an object store is configured
+    if let Some(store) = settings.s3.as_ref().map(S3Store::from_settings) {
+        let key = archive_file(&store, &pool, "exports", &output, format.content_type()).await?;
+        println!("Uploaded the export to {}", key);
+    }
+    Ok(())
+}
+
+// Hand the activities to `write` a page at a time, oldest first, so the export never
+// holds more than a page. Returns how many there were.
+async fn export_pages(
+    pool: &Pool,
+    mut write: impl FnMut(&[ActivityRow]) -> Result<(), Box<dyn Error>>,
+) -> Result<usize, Box<dyn Error>> {
+    let mut count = 0;
+    let mut cursor = None;
+    loop {
+        let conn = pool.get().await?;
+        let (sort, order) = (ActivitySort::StartDate, SortOrder::Asc);
+        let page = query_activities(conn, ActivityFilter::default(), sort, order, cursor, EXPORT_PAGE_SIZE).await?;
+        write(&page)?;
+        count += page.len();
+        if page.len() < EXPORT_PAGE_SIZE as usize {
+            return Ok(count);
+        }
+        cursor = page.last().map(|row| Cursor::for_row(row, sort, order));
+    }
+}
+
+// Streams are read one activity at a time and written a row group at a time
+async fn export_streams(settings: &Settings, pool: &Pool, writer: BufWriter<File>) -> Result<usize, Box<dyn Error>> {
+    let blob_store = BlobStore::from_settings(&settings.storage);
+    let mut parquet = ParquetWriter::streams(writer)?;
+    let mut buffered = Table::default();
+    let mut ids = Vec::new();
+    export_pages(pool, |page| {
+        ids.extend(page.iter().map(|activity| activity.id));
+        Ok(())
+    })
+    .await?;
+
+    let mut count = 0;
+    for activity_id in ids {
+        let Some(hash) = find_blob_ref(pool.get().await?, format!("activity:{}:streams", activity_id)).await? else {
+            continue;
+        };
+        let Ok(streams) = ActivityStreams::parse(&blob_store.get(&hash)?) else {
+            continue;
+        };
+        buffered.append(stream_table(activity_id, &streams));
+        count += 1;
+        if buffered.rows() >= STREAM_ROW_GROUP_SIZE {
+            parquet.write(std::mem::take(&mut buffered))?;
+        }
+    }
+    parquet.write(buffered)?;
+    parquet.finish()?;
+    Ok(count)
+}
+
+// Re-hash the blob store and exit with an error if anything is corrupted
+fn verify(settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let blob_store = BlobStore::from_settings(&settings.storage);
+    let report = blob_store.verify()?;
+    println!("Checked {} blobs, {} corrupted", report.checked, report.corrupted.len());
+    for hash in &report.corrupted {
+        println!("corrupted: {}", hash);
+    }
+    if !report.corrupted.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn status(settings: &Settings, sc: &StravaClient) -> Result<(), Box<dyn Error>> {
+    match sc.token_set() {
+        Ok(token_set) => {
+            let expires_at = chrono::DateTime::from_timestamp(token_set.expires_at, 0).unwrap_or_default();
+            let state = match expires_at > chrono::Utc::now() {
+                true => "valid",
+                false => "expired, refreshed on the next sync",
+            };
+            println!("Login:         access token {} until {}", state, expires_at);
+        }
+        Err(e) => println!("Login:         {}", e),
+    }
+
+    let pool = establish_connection(&settings.database);
+    let count = count_activities(pool.get().await?).await?;
+    let latest = latest_start_date(pool.get().await?).await?;
+    println!("Activities:    {} stored", count);
+    match latest {
+        Some(date) => println!("Latest:        {}", date),
+        None => println!("Latest:        never synced"),
+    }
+    println!("Blob store:    {}", settings.storage.blob_dir);
+    match &settings.s3 {
+        Some(s3) => println!("Object store:  {}/{}", s3.endpoint, s3.bucket),
+        None => println!("Object store:  not configured"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        let cli = Cli::try_parse_from(["strava-backup"]).unwrap();
+        assert!(cli.command.is_none());
+
+        let cli = Cli::try_parse_from(["strava-backup", "sync", "--no-streams"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Sync { no_streams: true })));
+
+        let cli = Cli::try_parse_from(["strava-backup", "export", "-o", "out.jsonl"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Export { format: ExportFormat::Jsonl, .. })));
+
+        assert!(Cli::try_parse_from(["strava-backup", "export"]).is_err());
+
+        let cli = Cli::try_parse_from(["strava-backup", "export", "--format", "kmz", "-o", "out.kmz"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Export { format: ExportFormat::Kmz, .. })));
+
+        let cli = Cli::try_parse_from(["strava-backup", "export", "--format", "streams-parquet", "-o", "s.parquet"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Export { format: ExportFormat::StreamsParquet, .. })));
+
+        let cli = Cli::try_parse_from(["strava-backup", "analyze", "--all"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Analyze { all: true })));
+
+        let cli = Cli::try_parse_from(["strava-backup", "gc"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Gc)));
+
+        let cli = Cli::try_parse_from(["strava-backup", "login", "--paste"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Login { paste: true, redirect_uri: None })));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::models::club::ClubActivityRow;
+use crate::stats::week_key;
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Week,
+    Month,
+    Year,
+}
+
+// Totals of the club in one period, `athletes` counts the distinct names
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ClubBucket {
+    pub key: String,
+    pub activities: i64,
+    pub athletes: usize,
+    pub distance: f64,
+    pub moving_time: i64,
+    pub elevation_gain: f64,
+}
+
+// A period's totals with the names of the athletes seen in it
+type Bucket<'a> = (ClubBucket, HashSet<(&'a str, &'a str)>);
+
+// Strava doesn't date club activities, so they go to the period they were first synced
+// in. That is close to when they were done as long as the sync runs often, the first
+// sync of a club puts its whole recent history into one period.
+pub fn timeline(activities: &[ClubActivityRow], period: Period) -> Vec<ClubBucket> {
+    let mut buckets: BTreeMap<String, Bucket> = BTreeMap::new();
+    for activity in activities {
+        let date = activity.first_seen_at.and_utc();
+        let key = match period {
+            Period::Week => week_key(date),
+            Period::Month => format!("{}-{:02}", date.year(), date.month()),
+            Period::Year => date.year().to_string(),
+        };
+        let (bucket, athletes) = buckets
+            .entry(key.clone())
+            .or_insert_with(|| (ClubBucket { key, ..Default::default() }, HashSet::new()));
+        bucket.activities += 1;
+        bucket.distance += activity.distance as f64;
+        bucket.moving_time += activity.moving_time as i64;
+        bucket.elevation_gain += activity.total_elevation_gain as f64;
+        athletes.insert((activity.firstname.as_str(), activity.lastname.as_str()));
+    }
+    buckets
+        .into_values()
+        .map(|(bucket, athletes)| ClubBucket { athletes: athletes.len(), ..bucket })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn activity(firstname: &str, seen: &str, distance: f32) -> ClubActivityRow {
+        ClubActivityRow {
+            key: format!("{}{}", firstname, seen),
+            club_id: 1,
+            firstname: firstname.to_string(),
+            lastname: "S.".to_string(),
+            name: "Ride".to_string(),
+            sport_type: Some("Ride".to_string()),
+            distance,
+            moving_time: 3600,
+            elapsed_time: 3700,
+            total_elevation_gain: 100.0,
+            workout_type: None,
+            first_seen_at: DateTime::parse_from_rfc3339(seen).unwrap().naive_utc(),
+        }
+    }
+
+    #[test]
+    fn test_timeline() {
+        let activities = vec![
+            activity("Peter", "2024-01-01T09:00:00Z", 40000.0),
+            activity("Peter", "2024-01-03T09:00:00Z", 20000.0),
+            activity("Jane", "2024-01-05T09:00:00Z", 30000.0),
+            activity("Jane", "2024-02-01T09:00:00Z", 10000.0),
+        ];
+        let weeks = timeline(&activities, Period::Week);
+        assert_eq!(weeks.iter().map(|bucket| bucket.key.as_str()).collect::<Vec<_>>(), vec!["2024-W01", "2024-W05"]);
+        assert_eq!((weeks[0].activities, weeks[0].athletes, weeks[0].distance), (3, 2, 90000.0));
+        assert_eq!((weeks[0].moving_time, weeks[0].elevation_gain), (10800, 300.0));
+
+        let years = timeline(&activities, Period::Year);
+        assert_eq!((years[0].key.as_str(), years[0].activities, years[0].athletes), ("2024", 4, 2));
+        assert!(timeline(&[], Period::Month).is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::settings::TokenSettings;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::collections::HashMap;
+
+const NONCE_LEN: usize = 12;
+
+// Encrypts tokens with AES-256-GCM. Sealed values look like "v1:<key id>:<base64>" so
+// old values can still be opened after the active key is rotated.
+pub struct TokenCipher {
+    active_key_id: String,
+    keys: HashMap<String, Key<Aes256Gcm>>,
+}
+
+impl TokenCipher {
+    pub fn init(active_key_id: &str, keys: HashMap<String, Vec<u8>>) -> Result<TokenCipher, &'static str> {
+        let mut cipher_keys = HashMap::new();
+        for (key_id, key) in keys {
+            if key.len() != 32 {
+                return Err("Token keys must be 32 bytes");
+            }
+            if key_id.is_empty() || key_id.contains(':') {
+                return Err("Token key ids can't be empty or contain ':'");
+            }
+            cipher_keys.insert(key_id, *Key::<Aes256Gcm>::from_slice(&key));
+        }
+        if !cipher_keys.contains_key(active_key_id) {
+            return Err("Active token key id not found in the key list");
+        }
+
+        Ok(TokenCipher {
+            active_key_id: active_key_id.to_string(),
+            keys: cipher_keys,
+        })
+    }
+
+    // tokens.keys is a comma separated list of "<key id>:<base64 key>", tokens.active_key_id
+    // the one used for new values. Old keys stay in the list until re-encryption is done.
+    pub fn from_settings(settings: &TokenSettings) -> Result<TokenCipher, &'static str> {
+        let mut keys = HashMap::new();
+        for entry in settings.keys.expose().split(',') {
+            let (key_id, encoded) = entry
+                .trim()
+                .split_once(':')
+                .ok_or("tokens.keys entries must look like <key id>:<base64 key>")?;
+            let key = STANDARD
+                .decode(encoded)
+                .map_err(|_| "tokens.keys contains invalid base64")?;
+            keys.insert(key_id.to_string(), key);
+        }
+
+        TokenCipher::init(&settings.active_key_id, keys)
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, &'static str> {
+        let cipher = Aes256Gcm::new(&self.keys[&self.active_key_id]);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: plaintext.as_bytes(),
+            aad: self.active_key_id.as_bytes(),
+        };
+        let ciphertext = cipher
+            .encrypt(&nonce, payload)
+            .map_err(|_| "Could not encrypt token")?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(format!("v1:{}:{}", self.active_key_id, STANDARD.encode(sealed)))
+    }
+
+    pub fn decrypt(&self, sealed: &str) -> Result<String, &'static str> {
+        let (key_id, encoded) = match sealed.split(':').collect::<Vec<&str>>()[..] {
+            ["v1", key_id, encoded] => (key_id, encoded),
+            _ => return Err("Token is not encrypted"),
+        };
+        let key = self.keys.get(key_id).ok_or("Token encrypted with an unknown key")?;
+        let bytes = STANDARD.decode(encoded).map_err(|_| "Token is not valid base64")?;
+        if bytes.len() < NONCE_LEN {
+            return Err("Token is too short");
+        }
+
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: key_id.as_bytes(),
+        };
+        let plaintext = Aes256Gcm::new(key)
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| "Could not decrypt token")?;
+        String::from_utf8(plaintext).map_err(|_| "Decrypted token is not utf8")
+    }
+
+    pub fn is_current(&self, sealed: &str) -> bool {
+        sealed.starts_with(&format!("v1:{}:", self.active_key_id))
+    }
+
+    // Move a value to the active key. Values that are not encrypted yet (written before
+    // encryption existed) are encrypted as they are.
+    pub fn reencrypt(&self, value: &str) -> Result<String, &'static str> {
+        if self.is_current(value) {
+            return Ok(value.to_string());
+        }
+        let plaintext = match self.decrypt(value) {
+            Ok(plaintext) => plaintext,
+            Err("Token is not encrypted") => value.to_string(),
+            Err(e) => return Err(e),
+        };
+        self.encrypt(&plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher(active: &str) -> TokenCipher {
+        let keys = HashMap::from([
+            ("2025".to_string(), vec![1u8; 32]),
+            ("2026".to_string(), vec![2u8; 32]),
+        ]);
+        TokenCipher::init(active, keys).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let cipher = cipher("2026");
+        let sealed = cipher.encrypt("refresh-me").unwrap();
+
+        assert!(sealed.starts_with("v1:2026:"));
+        assert!(!sealed.contains("refresh-me"));
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), "refresh-me");
+    }
+
+    #[test]
+    fn test_tampered_key_id_fails() {
+        let cipher = cipher("2026");
+        let sealed = cipher.encrypt("refresh-me").unwrap();
+        let tampered = sealed.replacen("v1:2026:", "v1:2025:", 1);
+
+        assert!(cipher.decrypt(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_reencrypt_moves_to_active_key() {
+        let old = cipher("2025").encrypt("refresh-me").unwrap();
+        let cipher = cipher("2026");
+
+        let rotated = cipher.reencrypt(&old).unwrap();
+        assert!(cipher.is_current(&rotated));
+        assert_eq!(cipher.decrypt(&rotated).unwrap(), "refresh-me");
+
+        let legacy = cipher.reencrypt("plain-token").unwrap();
+        assert_eq!(cipher.decrypt(&legacy).unwrap(), "plain-token");
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let short = HashMap::from([("a".to_string(), vec![1u8; 16])]);
+        assert!(TokenCipher::init("a", short).is_err());
+
+        let missing_active = HashMap::from([("a".to_string(), vec![1u8; 32])]);
+        assert!(TokenCipher::init("b", missing_active).is_err());
+    }
+}
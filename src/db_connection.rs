@@ -1,21 +1,11 @@
-use diesel::pg::Pg;
-use diesel::prelude::*;
-use std::env;
-use diesel::{Connection, PgConnection};
-use dotenv::dotenv;
-use diesel::prelude::*;
-
 use deadpool_diesel::postgres::Manager;
 use deadpool_diesel::postgres::Pool;
+use crate::settings::DatabaseSettings;
 
-pub fn establish_connection() -> Pool {
-    dotenv().ok(); //TODO: Replace by the settings thingy
-
-    let database_url = env::var("DATABASE_URL").expect("Database_url not set");
-    let manager = Manager::new(database_url, deadpool_diesel::Runtime::Tokio1);
-    let pool = Pool::builder(manager)
-        .max_size(10)
+pub fn establish_connection(settings: &DatabaseSettings) -> Pool {
+    let manager = Manager::new(settings.url.expose(), deadpool_diesel::Runtime::Tokio1);
+    Pool::builder(manager)
+        .max_size(settings.pool_size)
         .build()
-        .unwrap();
-    return pool
+        .unwrap()
 }
@@ -0,0 +1,399 @@
+use crate::models::activity::ActivityRow;
+use crate::models::social::{CommentRow, KudosRow};
+use crate::strava::parsers::ActivityStreams;
+use chrono::{DateTime, Utc};
+use parquet::basic::Compression;
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DataType, DoubleType, FloatType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+use parquet::schema::parser::parse_message_type;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+use std::sync::Arc;
+
+// Buffered stream rows before they are written as a row group
+pub const STREAM_ROW_GROUP_SIZE: usize = 100_000;
+
+const ACTIVITY_SCHEMA: &str = "
+message activity {
+    required int64 id;
+    required int64 athlete_id;
+    required binary name (STRING);
+    optional binary sport_type (STRING);
+    required int64 start_date (TIMESTAMP(MILLIS,true));
+    required float distance;
+    required int32 moving_time;
+    required int32 elapsed_time;
+    optional float total_elevation_gain;
+    optional float calories;
+    optional binary gear_id (STRING);
+    required boolean commute;
+    required boolean trainer;
+    optional binary description (STRING);
+    optional binary private_note (STRING);
+    optional binary summary_polyline (STRING);
+    required int64 comment_count;
+    required int64 kudos_count;
+}";
+
+// Long format, a row per sample. Only time is always there, the rest depends on the
+// device and the sport.
+const STREAM_SCHEMA: &str = "
+message stream {
+    required int64 activity_id;
+    required int32 t;
+    optional double lat;
+    optional double lng;
+    optional float alt;
+    optional float distance;
+    optional float velocity_smooth;
+    optional float hr;
+    optional float cadence;
+    optional float watts;
+    optional float temp;
+    optional float grade_smooth;
+    optional boolean moving;
+}";
+
+// Strava stream type of each numeric stream column
+const STREAM_NUMBERS: [(&str, &str); 8] = [
+    ("alt", "altitude"),
+    ("distance", "distance"),
+    ("velocity_smooth", "velocity_smooth"),
+    ("hr", "heartrate"),
+    ("cadence", "cadence"),
+    ("watts", "watts"),
+    ("temp", "temp"),
+    ("grade_smooth", "grade_smooth"),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Column {
+    Int64(Vec<Option<i64>>),
+    Int32(Vec<Option<i32>>),
+    Float(Vec<Option<f32>>),
+    Double(Vec<Option<f64>>),
+    Bool(Vec<Option<bool>>),
+    Text(Vec<Option<String>>),
+    // Milliseconds in parquet, RFC 3339 in csv
+    Timestamp(Vec<Option<DateTime<Utc>>>),
+}
+
+impl Column {
+    fn len(&self) -> usize {
+        match self {
+            Column::Int64(values) => values.len(),
+            Column::Int32(values) => values.len(),
+            Column::Float(values) => values.len(),
+            Column::Double(values) => values.len(),
+            Column::Bool(values) => values.len(),
+            Column::Text(values) => values.len(),
+            Column::Timestamp(values) => values.len(),
+        }
+    }
+
+    // Empty for nulls, like pandas and duckdb expect
+    fn cell(&self, row: usize) -> String {
+        match self {
+            Column::Int64(values) => values[row].map(|v| v.to_string()),
+            Column::Int32(values) => values[row].map(|v| v.to_string()),
+            Column::Float(values) => values[row].map(|v| v.to_string()),
+            Column::Double(values) => values[row].map(|v| v.to_string()),
+            Column::Bool(values) => values[row].map(|v| v.to_string()),
+            Column::Text(values) => values[row].clone(),
+            Column::Timestamp(values) => values[row].map(|v| v.to_rfc3339()),
+        }
+        .unwrap_or_default()
+    }
+
+    fn append(&mut self, other: Column) {
+        match (self, other) {
+            (Column::Int64(values), Column::Int64(other)) => values.extend(other),
+            (Column::Int32(values), Column::Int32(other)) => values.extend(other),
+            (Column::Float(values), Column::Float(other)) => values.extend(other),
+            (Column::Double(values), Column::Double(other)) => values.extend(other),
+            (Column::Bool(values), Column::Bool(other)) => values.extend(other),
+            (Column::Text(values), Column::Text(other)) => values.extend(other),
+            (Column::Timestamp(values), Column::Timestamp(other)) => values.extend(other),
+            _ => panic!("Appending a column of another type"),
+        }
+    }
+
+    fn write(self, column: &mut SerializedColumnWriter) -> parquet::errors::Result<()> {
+        match self {
+            Column::Int64(values) => write_values::<Int64Type>(column, values),
+            Column::Int32(values) => write_values::<Int32Type>(column, values),
+            Column::Float(values) => write_values::<FloatType>(column, values),
+            Column::Double(values) => write_values::<DoubleType>(column, values),
+            Column::Bool(values) => write_values::<BoolType>(column, values),
+            Column::Text(values) => write_values::<ByteArrayType>(
+                column,
+                values.into_iter().map(|v| v.map(|text| ByteArray::from(text.into_bytes()))).collect(),
+            ),
+            Column::Timestamp(values) => {
+                write_values::<Int64Type>(column, values.into_iter().map(|v| v.map(|date| date.timestamp_millis())).collect())
+            }
+        }
+    }
+}
+
+// Nulls are left out of the values and marked in the definition levels, required
+// columns have no levels
+fn write_values<T: DataType>(column: &mut SerializedColumnWriter, values: Vec<Option<T::T>>) -> parquet::errors::Result<()> {
+    let levels: Vec<i16> = values.iter().map(|value| value.is_some() as i16).collect();
+    let present: Vec<T::T> = values.into_iter().flatten().collect();
+    let writer = column.typed::<T>();
+    let levels = (writer.get_descriptor().max_def_level() > 0).then_some(levels.as_slice());
+    writer.write_batch(&present, levels, None)?;
+    Ok(())
+}
+
+// Columns in the order of their schema
+#[derive(Debug, Default, PartialEq)]
+pub struct Table {
+    pub columns: Vec<(&'static str, Column)>,
+}
+
+impl Table {
+    pub fn rows(&self) -> usize {
+        self.columns.first().map(|(_, column)| column.len()).unwrap_or_default()
+    }
+
+    pub fn append(&mut self, other: Table) {
+        if self.columns.is_empty() {
+            *self = other;
+            return;
+        }
+        for ((_, column), (_, other)) in self.columns.iter_mut().zip(other.columns) {
+            column.append(other);
+        }
+    }
+}
+
+// An activity of the JSON lines export, with its comments and kudos
+#[derive(Serialize)]
+pub struct ActivityRecord<'a> {
+    #[serde(flatten)]
+    pub activity: &'a ActivityRow,
+    pub comments: Vec<&'a CommentRow>,
+    pub kudos: Vec<&'a KudosRow>,
+}
+
+pub fn activity_records<'a>(
+    activities: &'a [ActivityRow],
+    comments: &'a [CommentRow],
+    kudos: &'a [KudosRow],
+) -> Vec<ActivityRecord<'a>> {
+    let mut records: Vec<ActivityRecord> =
+        activities.iter().map(|activity| ActivityRecord { activity, comments: vec![], kudos: vec![] }).collect();
+    let index: HashMap<i64, usize> = activities.iter().enumerate().map(|(i, activity)| (activity.id, i)).collect();
+    for comment in comments {
+        if let Some(i) = index.get(&comment.activity_id) {
+            records[*i].comments.push(comment);
+        }
+    }
+    for kudo in kudos {
+        if let Some(i) = index.get(&kudo.activity_id) {
+            records[*i].kudos.push(kudo);
+        }
+    }
+    records
+}
+
+// `social` holds the comment and kudos counts by activity, see `social_counts`
+pub fn activity_table(activities: &[ActivityRow], social: &HashMap<i64, (i64, i64)>) -> Table {
+    let counts: Vec<(i64, i64)> =
+        activities.iter().map(|a| social.get(&a.id).copied().unwrap_or_default()).collect();
+    Table {
+        columns: vec![
+            ("id", Column::Int64(activities.iter().map(|a| Some(a.id)).collect())),
+            ("athlete_id", Column::Int64(activities.iter().map(|a| Some(a.athlete_id)).collect())),
+            ("name", Column::Text(activities.iter().map(|a| Some(a.name.clone())).collect())),
+            ("sport_type", Column::Text(activities.iter().map(|a| a.sport_type.clone()).collect())),
+            ("start_date", Column::Timestamp(activities.iter().map(|a| Some(a.start_date)).collect())),
+            ("distance", Column::Float(activities.iter().map(|a| Some(a.distance)).collect())),
+            ("moving_time", Column::Int32(activities.iter().map(|a| Some(a.moving_time)).collect())),
+            ("elapsed_time", Column::Int32(activities.iter().map(|a| Some(a.elapsed_time)).collect())),
+            ("total_elevation_gain", Column::Float(activities.iter().map(|a| a.total_elevation_gain).collect())),
+            ("calories", Column::Float(activities.iter().map(|a| a.calories).collect())),
+            ("gear_id", Column::Text(activities.iter().map(|a| a.gear_id.clone()).collect())),
+            ("commute", Column::Bool(activities.iter().map(|a| Some(a.commute)).collect())),
+            ("trainer", Column::Bool(activities.iter().map(|a| Some(a.trainer)).collect())),
+            ("description", Column::Text(activities.iter().map(|a| a.description.clone()).collect())),
+            ("private_note", Column::Text(activities.iter().map(|a| a.private_note.clone()).collect())),
+            ("summary_polyline", Column::Text(activities.iter().map(|a| a.summary_polyline.clone()).collect())),
+            ("comment_count", Column::Int64(counts.iter().map(|c| Some(c.0)).collect())),
+            ("kudos_count", Column::Int64(counts.iter().map(|c| Some(c.1)).collect())),
+        ],
+    }
+}
+
+// A row per sample of the time stream, empty without one
+pub fn stream_table(activity_id: i64, streams: &ActivityStreams) -> Table {
+    let time = streams.numbers("time").unwrap_or_default();
+    let latlng = streams.latlng();
+    let moving = streams.flags("moving");
+
+    let mut columns = vec![
+        ("activity_id", Column::Int64(vec![Some(activity_id); time.len()])),
+        ("t", Column::Int32(time.iter().map(|t| Some(*t as i32)).collect())),
+        ("lat", Column::Double((0..time.len()).map(|i| latlng.and_then(|l| l.get(i)).map(|p| p[0])).collect())),
+        ("lng", Column::Double((0..time.len()).map(|i| latlng.and_then(|l| l.get(i)).map(|p| p[1])).collect())),
+    ];
+    for (name, stream_type) in STREAM_NUMBERS {
+        let values = streams.numbers(stream_type);
+        columns.push((name, Column::Float((0..time.len()).map(|i| values.and_then(|v| v.get(i)).copied()).collect())));
+    }
+    columns.push(("moving", Column::Bool((0..time.len()).map(|i| moving.and_then(|m| m.get(i)).copied()).collect())));
+    Table { columns }
+}
+
+pub struct CsvWriter<W: Write> {
+    writer: csv::Writer<W>,
+    header: bool,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(writer: W) -> CsvWriter<W> {
+        CsvWriter { writer: csv::Writer::from_writer(writer), header: false }
+    }
+
+    pub fn write(&mut self, table: &Table) -> Result<(), Box<dyn Error>> {
+        if !self.header {
+            self.writer.write_record(table.columns.iter().map(|(name, _)| *name))?;
+            self.header = true;
+        }
+        for row in 0..table.rows() {
+            self.writer.write_record(table.columns.iter().map(|(_, column)| column.cell(row)))?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// Every write is a row group, so memory stays at one batch however big the export
+pub struct ParquetWriter<W: Write + Send> {
+    writer: SerializedFileWriter<W>,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    pub fn activities(writer: W) -> Result<ParquetWriter<W>, Box<dyn Error>> {
+        ParquetWriter::new(writer, ACTIVITY_SCHEMA)
+    }
+
+    pub fn streams(writer: W) -> Result<ParquetWriter<W>, Box<dyn Error>> {
+        ParquetWriter::new(writer, STREAM_SCHEMA)
+    }
+
+    fn new(writer: W, schema: &str) -> Result<ParquetWriter<W>, Box<dyn Error>> {
+        let schema = Arc::new(parse_message_type(schema)?);
+        let properties = Arc::new(WriterProperties::builder().set_compression(Compression::SNAPPY).build());
+        Ok(ParquetWriter { writer: SerializedFileWriter::new(writer, schema, properties)? })
+    }
+
+    pub fn write(&mut self, table: Table) -> Result<(), Box<dyn Error>> {
+        if table.rows() == 0 {
+            return Ok(());
+        }
+        let mut row_group = self.writer.next_row_group()?;
+        for (_, column) in table.columns {
+            let mut column_writer = row_group.next_column()?.ok_or("More columns than in the schema")?;
+            column.write(&mut column_writer)?;
+            column_writer.close()?;
+        }
+        row_group.close()?;
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::tests::activity_row;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    fn streams() -> ActivityStreams {
+        let streams = r#"[{"type": "time", "data": [0, 1, 3], "series_type": "time", "original_size": 3, "resolution": "high"},
+            {"type": "latlng", "data": [[50.1, 4.1], [50.2, 4.2], [50.3, 4.3]], "series_type": "time", "original_size": 3, "resolution": "high"},
+            {"type": "heartrate", "data": [120, 130, 140], "series_type": "time", "original_size": 3, "resolution": "high"},
+            {"type": "moving", "data": [false, true, true], "series_type": "time", "original_size": 3, "resolution": "high"}]"#;
+        ActivityStreams::parse(streams.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut ride = activity_row(1, "2024-01-01T09:00:00Z", "Ride", 40000.0);
+        ride.name = "Morning, \"easy\" ride".to_string();
+        ride.calories = None;
+
+        let mut bytes = Vec::new();
+        let mut writer = CsvWriter::new(&mut bytes);
+        let social = HashMap::from([(1, (2, 5))]);
+        writer.write(&activity_table(&[ride], &social)).unwrap();
+        writer.write(&activity_table(&[activity_row(2, "2024-01-02T09:00:00Z", "Run", 10000.0)], &social)).unwrap();
+        writer.finish().unwrap();
+
+        let csv = String::from_utf8(bytes).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("id,athlete_id,name,sport_type,start_date,distance"));
+        assert!(lines[1].starts_with("1,28853829,\"Morning, \"\"easy\"\" ride\",Ride,2024-01-01T09:00:00+00:00,40000,3600,3700,100,,"));
+        assert!(lines[0].ends_with(",comment_count,kudos_count"));
+        assert!(lines[1].ends_with(",2,5"));
+        assert!(lines[2].starts_with("2,28853829,Activity 2,Run"));
+        assert!(lines[2].ends_with(",0,0"));
+    }
+
+    #[test]
+    fn test_stream_table() {
+        let table = stream_table(7, &streams());
+        assert_eq!(table.rows(), 3);
+        assert_eq!(table.columns.len(), 13);
+        assert_eq!(table.columns[1], ("t", Column::Int32(vec![Some(0), Some(1), Some(3)])));
+        assert_eq!(table.columns[2], ("lat", Column::Double(vec![Some(50.1), Some(50.2), Some(50.3)])));
+        let hr = table.columns.iter().find(|(name, _)| *name == "hr").unwrap();
+        assert_eq!(hr.1, Column::Float(vec![Some(120.0), Some(130.0), Some(140.0)]));
+        let watts = table.columns.iter().find(|(name, _)| *name == "watts").unwrap();
+        assert_eq!(watts.1, Column::Float(vec![None, None, None]));
+
+        let mut both = stream_table(7, &streams());
+        both.append(table);
+        assert_eq!(both.rows(), 6);
+        assert_eq!(stream_table(7, &ActivityStreams { streams: vec![] }).rows(), 0);
+    }
+
+    #[test]
+    fn test_parquet() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = ParquetWriter::streams(file.reopen().unwrap()).unwrap();
+        writer.write(stream_table(7, &streams())).unwrap();
+        writer.write(stream_table(8, &streams())).unwrap();
+        writer.finish().unwrap();
+
+        let reader = SerializedFileReader::new(file.reopen().unwrap()).unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 2);
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 6);
+        let rows: Vec<String> = reader.get_row_iter(None).unwrap().map(|row| row.unwrap().to_string()).collect();
+        assert!(rows[0].starts_with("{activity_id: 7, t: 0, lat: 50.1, lng: 4.1, alt: null"));
+        assert!(rows[5].contains("activity_id: 8"));
+        assert!(rows[5].ends_with("moving: true}"));
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = ParquetWriter::activities(file.reopen().unwrap()).unwrap();
+        writer.write(activity_table(&[activity_row(1, "2024-01-01T09:00:00Z", "Ride", 40000.0)], &HashMap::new())).unwrap();
+        writer.finish().unwrap();
+        let reader = SerializedFileReader::new(file.reopen().unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 1);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::models::gear::{ComponentRow, GearActivityRow, GearRow};
+use serde::Serialize;
+use std::collections::HashMap;
+
+// Distance in meters and moving time in seconds from the stored activities, which can
+// differ from strava's own distance when the history isn't fully synced
+#[derive(Serialize)]
+pub struct GearUsage {
+    #[serde(flatten)]
+    pub gear: GearRow,
+    pub activities: usize,
+    pub distance: f64,
+    pub moving_time: i64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ComponentStatus {
+    #[serde(flatten)]
+    pub component: ComponentRow,
+    pub activities: usize,
+    pub distance: f64,
+    pub moving_time: i64,
+    // The largest share of a service interval used up, None without intervals
+    pub wear: Option<f64>,
+    pub due: bool,
+}
+
+pub fn gear_usage(gear: Vec<GearRow>, activities: &[GearActivityRow]) -> Vec<GearUsage> {
+    let mut totals: HashMap<&str, (usize, f64, i64)> = HashMap::new();
+    for activity in activities {
+        let total = totals.entry(activity.gear_id.as_str()).or_default();
+        total.0 += 1;
+        total.1 += activity.distance as f64;
+        total.2 += activity.moving_time as i64;
+    }
+    gear.into_iter()
+        .map(|gear| {
+            let (count, distance, moving_time) = totals.get(gear.id.as_str()).copied().unwrap_or_default();
+            GearUsage { gear, activities: count, distance, moving_time }
+        })
+        .collect()
+}
+
+// Activities on the component's gear from the day it was installed until the day it
+// was removed, a replacement installed that day gets that day's activities
+fn is_used(component: &ComponentRow, activity: &GearActivityRow) -> bool {
+    let day = activity.start_date.date_naive();
+    activity.gear_id == component.gear_id
+        && day >= component.installed_at
+        && component.removed_at.is_none_or(|removed| day < removed)
+}
+
+pub fn component_status(component: ComponentRow, activities: &[GearActivityRow]) -> ComponentStatus {
+    let used: Vec<&GearActivityRow> = activities.iter().filter(|activity| is_used(&component, activity)).collect();
+    let distance: f64 = used.iter().map(|activity| activity.distance as f64).sum();
+    let moving_time: i64 = used.iter().map(|activity| activity.moving_time as i64).sum();
+
+    let shares = [
+        component.service_distance.map(|interval| distance / interval as f64),
+        component.service_time.map(|interval| moving_time as f64 / interval as f64),
+    ];
+    let wear = shares.into_iter().flatten().reduce(f64::max);
+    let due = component.removed_at.is_none() && wear.is_some_and(|wear| wear >= 1.0);
+    ComponentStatus { activities: used.len(), component, distance, moving_time, wear, due }
+}
+
+// Installed components past `threshold` of an interval, the most worn first
+pub fn due_components(statuses: Vec<ComponentStatus>, threshold: f64) -> Vec<ComponentStatus> {
+    let mut due: Vec<ComponentStatus> = statuses
+        .into_iter()
+        .filter(|status| status.component.removed_at.is_none() && status.wear.is_some_and(|wear| wear >= threshold))
+        .collect();
+    due.sort_by(|a, b| b.wear.partial_cmp(&a.wear).unwrap_or(std::cmp::Ordering::Equal));
+    due
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, NaiveDate};
+
+    fn ride(gear_id: &str, date: &str, distance: f32, moving_time: i32) -> GearActivityRow {
+        GearActivityRow {
+            gear_id: gear_id.to_string(),
+            start_date: DateTime::parse_from_rfc3339(date).unwrap().to_utc(),
+            distance,
+            moving_time,
+        }
+    }
+
+    fn rides() -> Vec<GearActivityRow> {
+        vec![
+            ride("b1", "2024-01-01T09:00:00Z", 50000.0, 7200),
+            ride("b1", "2024-02-01T09:00:00Z", 100000.0, 14400),
+            ride("b2", "2024-02-02T09:00:00Z", 30000.0, 3600),
+            ride("b1", "2024-03-01T09:00:00Z", 80000.0, 10800),
+        ]
+    }
+
+    fn component(id: i32, installed_at: &str, removed_at: Option<&str>) -> ComponentRow {
+        ComponentRow {
+            id,
+            gear_id: "b1".to_string(),
+            kind: "chain".to_string(),
+            name: None,
+            installed_at: installed_at.parse::<NaiveDate>().unwrap(),
+            removed_at: removed_at.map(|date| date.parse::<NaiveDate>().unwrap()),
+            service_distance: Some(150000.0),
+            service_time: None,
+            created_at: DateTime::UNIX_EPOCH.naive_utc(),
+        }
+    }
+
+    #[test]
+    fn test_gear_usage() {
+        let gear = ["b1", "b3"]
+            .map(|id| GearRow {
+                id: id.to_string(),
+                athlete_id: 1,
+                kind: "bike".to_string(),
+                name: None,
+                brand_name: None,
+                model_name: None,
+                description: None,
+                is_primary: false,
+                retired: false,
+                distance: 0.0,
+                updated_at: DateTime::UNIX_EPOCH.naive_utc(),
+            })
+            .to_vec();
+        let usage = gear_usage(gear, &rides());
+        assert_eq!((usage[0].activities, usage[0].distance, usage[0].moving_time), (3, 230000.0, 32400));
+        assert_eq!((usage[1].activities, usage[1].distance), (0, 0.0));
+    }
+
+    #[test]
+    fn test_component_status() {
+        // Installed the day of the second ride, still on
+        let status = component_status(component(1, "2024-02-01", None), &rides());
+        assert_eq!((status.activities, status.distance, status.moving_time), (2, 180000.0, 25200));
+        assert_eq!(status.wear, Some(1.2));
+        assert!(status.due);
+
+        // Swapped out on the day of the second ride, so that ride isn't on it
+        let status = component_status(component(2, "2023-12-01", Some("2024-02-01")), &rides());
+        assert_eq!((status.activities, status.distance), (1, 50000.0));
+        assert!(!status.due);
+
+        // The interval reached first counts
+        let mut tyre = component(3, "2024-01-01", None);
+        tyre.service_time = Some(18000);
+        let status = component_status(tyre, &rides());
+        assert_eq!(status.wear, Some(1.8));
+
+        let mut untracked = component(4, "2024-01-01", None);
+        untracked.service_distance = None;
+        let status = component_status(untracked, &rides());
+        assert_eq!((status.wear, status.due), (None, false));
+    }
+
+    #[test]
+    fn test_due_components() {
+        let statuses = vec![
+            component_status(component(1, "2024-02-01", None), &rides()),
+            component_status(component(2, "2023-12-01", Some("2024-02-01")), &rides()),
+            component_status(component(3, "2024-03-01", None), &rides()),
+            component_status(component(4, "2024-01-01", None), &rides()),
+        ];
+        let due = due_components(statuses, 0.5);
+        assert_eq!(due.iter().map(|status| status.component.id).collect::<Vec<_>>(), vec![4, 1, 3]);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::models::activity::ActivityRow;
+use serde_json::{Value, json};
+
+// The activity as a feature with its stats as properties, None without a map
+pub fn feature(activity: &ActivityRow) -> Option<Value> {
+    let (source, points) = activity.track()?;
+    // GeoJSON positions are [lng, lat], the other way around from strava
+    let coordinates: Vec<[f64; 2]> = points.iter().map(|[lat, lng]| [*lng, *lat]).collect();
+    let geometry = match coordinates.as_slice() {
+        [point] => json!({ "type": "Point", "coordinates": point }),
+        _ => json!({ "type": "LineString", "coordinates": coordinates }),
+    };
+    Some(json!({
+        "type": "Feature",
+        "id": activity.id,
+        "geometry": geometry,
+        "properties": {
+            "name": activity.name,
+            "sport_type": activity.sport_type,
+            "start_date": activity.start_date,
+            "distance": activity.distance,
+            "moving_time": activity.moving_time,
+            "elapsed_time": activity.elapsed_time,
+            "total_elevation_gain": activity.total_elevation_gain,
+            "track_source": source,
+        },
+    }))
+}
+
+// Activities without a map are left out
+pub fn feature_collection(activities: &[ActivityRow]) -> Value {
+    let features: Vec<Value> = activities.iter().filter_map(feature).collect();
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::tests::activity_row;
+
+    #[test]
+    fn test_feature() {
+        let mut activity = activity_row(1, "2024-01-01T09:00:00Z", "Ride", 1000.0);
+        activity.summary_polyline = Some("_p~iF~ps|U_ulLnnqC_mqNvxq`@".to_string());
+
+        let feature = feature(&activity).unwrap();
+        assert_eq!(feature["geometry"]["type"], "LineString");
+        assert_eq!(feature["geometry"]["coordinates"][0], json!([-120.2, 38.5]));
+        assert_eq!(feature["properties"]["track_source"], "summary_polyline");
+        assert_eq!(feature["properties"]["sport_type"], "Ride");
+
+        // The streams win over the maps from strava
+        activity.track_polyline = Some("_p~iF~ps|U".to_string());
+        let feature = super::feature(&activity).unwrap();
+        assert_eq!(feature["geometry"], json!({ "type": "Point", "coordinates": [-120.2, 38.5] }));
+        assert_eq!(feature["properties"]["track_source"], "streams");
+    }
+
+    #[test]
+    fn test_feature_collection() {
+        let mut with_map = activity_row(1, "2024-01-01T09:00:00Z", "Ride", 1000.0);
+        with_map.polyline = Some("_p~iF~ps|U_ulLnnqC".to_string());
+        let mut broken_map = activity_row(2, "2024-01-02T09:00:00Z", "Ride", 1000.0);
+        broken_map.summary_polyline = Some("_p~iF".to_string());
+        let indoor = activity_row(3, "2024-01-03T09:00:00Z", "VirtualRide", 1000.0);
+
+        let collection = feature_collection(&[with_map, broken_map, indoor]);
+        assert_eq!(collection["type"], "FeatureCollection");
+        assert_eq!(collection["features"].as_array().unwrap().len(), 1);
+        assert_eq!(collection["features"][0]["id"], 1);
+    }
+}
@@ -0,0 +1,291 @@
+use crate::models::heatmap::HeatmapFilter;
+use crate::settings::HeatmapSettings;
+use crate::storage::content_hash;
+use std::f64::consts::PI;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+pub const TILE_SIZE: usize = 256;
+
+// Web mercator stops here, the map is a square
+const MAX_LAT: f64 = 85.05112878;
+
+// Activities through a pixel past which it doesn't get any brighter
+const SATURATION: f64 = 50.0;
+
+// From dark red for a single pass to pale yellow for the usual roads
+const RAMP: [(f64, [u8; 3]); 3] = [(0.0, [160, 20, 10]), (0.5, [255, 110, 0]), (1.0, [255, 250, 190])];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min_lat: f64,
+    pub min_lng: f64,
+    pub max_lat: f64,
+    pub max_lng: f64,
+}
+
+pub fn track_bounds(track: &[[f64; 2]]) -> Option<Bounds> {
+    let first = track.first()?;
+    let start = Bounds { min_lat: first[0], min_lng: first[1], max_lat: first[0], max_lng: first[1] };
+    Some(track.iter().fold(start, |bounds, [lat, lng]| Bounds {
+        min_lat: bounds.min_lat.min(*lat),
+        min_lng: bounds.min_lng.min(*lng),
+        max_lat: bounds.max_lat.max(*lat),
+        max_lng: bounds.max_lng.max(*lng),
+    }))
+}
+
+// XYZ tile as in openstreetmap, y grows southwards
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tile {
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Tile {
+    // None when the tile is outside of the map at that zoom
+    pub fn new(z: u8, x: u32, y: u32) -> Option<Tile> {
+        let count = 1u64.checked_shl(z as u32)?;
+        match (x as u64) < count && (y as u64) < count {
+            true => Some(Tile { z, x, y }),
+            false => None,
+        }
+    }
+
+    // Width of the whole map in pixels at this zoom
+    fn map_size(&self) -> f64 {
+        TILE_SIZE as f64 * 2f64.powi(self.z as i32)
+    }
+
+    // Position in the tile in pixels, outside of 0..TILE_SIZE when the point isn't on it
+    pub fn pixel(&self, lat: f64, lng: f64) -> (f64, f64) {
+        let size = self.map_size();
+        let sin = lat.clamp(-MAX_LAT, MAX_LAT).to_radians().sin();
+        let x = (lng + 180.0) / 360.0 * size;
+        let y = (0.5 - ((1.0 + sin) / (1.0 - sin)).ln() / (4.0 * PI)) * size;
+        (x - self.x as f64 * TILE_SIZE as f64, y - self.y as f64 * TILE_SIZE as f64)
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        let count = 2f64.powi(self.z as i32);
+        let lng = |x: u32| x as f64 / count * 360.0 - 180.0;
+        let lat = |y: u32| (PI * (1.0 - 2.0 * y as f64 / count)).sinh().atan().to_degrees();
+        Bounds { min_lat: lat(self.y + 1), min_lng: lng(self.x), max_lat: lat(self.y), max_lng: lng(self.x + 1) }
+    }
+}
+
+// How many activities went through each pixel of a tile, row by row. An activity counts
+// once per pixel however many times it passed there, so laps don't light up a track.
+pub struct Density {
+    tile: Tile,
+    counts: Vec<u32>,
+    // Last track that marked the pixel
+    marked_by: Vec<usize>,
+    tracks: usize,
+}
+
+impl Density {
+    pub fn new(tile: Tile) -> Density {
+        Density { tile, counts: vec![0; TILE_SIZE * TILE_SIZE], marked_by: vec![usize::MAX; TILE_SIZE * TILE_SIZE], tracks: 0 }
+    }
+
+    pub fn add(&mut self, track: &[[f64; 2]]) {
+        let points: Vec<(f64, f64)> = track.iter().map(|[lat, lng]| self.tile.pixel(*lat, *lng)).collect();
+        if let [point] = points.as_slice() {
+            self.mark(*point);
+        }
+        for segment in points.windows(2) {
+            if let Some((from, to)) = clip(segment[0], segment[1]) {
+                // A step per pixel along the longest axis leaves no holes
+                let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).ceil().max(1.0) as usize;
+                for step in 0..=steps {
+                    let t = step as f64 / steps as f64;
+                    self.mark((from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t));
+                }
+            }
+        }
+        self.tracks += 1;
+    }
+
+    fn mark(&mut self, (x, y): (f64, f64)) {
+        if !(0.0..TILE_SIZE as f64).contains(&x) || !(0.0..TILE_SIZE as f64).contains(&y) {
+            return;
+        }
+        let index = y as usize * TILE_SIZE + x as usize;
+        if self.marked_by[index] != self.tracks {
+            self.marked_by[index] = self.tracks;
+            self.counts[index] += 1;
+        }
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let rgba: Vec<u8> = self.counts.iter().flat_map(|count| color(*count)).collect();
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, TILE_SIZE as u32, TILE_SIZE as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&rgba)?;
+        writer.finish()?;
+        Ok(bytes)
+    }
+}
+
+// The part of the segment on the tile (Liang-Barsky), so a long straight line at a high
+// zoom doesn't walk millions of pixels off the tile
+fn clip(from: (f64, f64), to: (f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let size = TILE_SIZE as f64;
+    let (mut enter, mut exit) = (0.0f64, 1.0f64);
+    for (p, q) in [(-dx, from.0), (dx, size - from.0), (-dy, from.1), (dy, size - from.1)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            enter = enter.max(q / p);
+        } else {
+            exit = exit.min(q / p);
+        }
+    }
+    match enter <= exit {
+        true => Some(((from.0 + dx * enter, from.1 + dy * enter), (from.0 + dx * exit, from.1 + dy * exit))),
+        false => None,
+    }
+}
+
+// Transparent without activities, then on a log scale so a single pass still shows
+fn color(count: u32) -> [u8; 4] {
+    if count == 0 {
+        return [0; 4];
+    }
+    let t = ((count as f64).ln_1p() / SATURATION.ln_1p()).min(1.0);
+    let stop = RAMP.windows(2).position(|pair| t <= pair[1].0).unwrap_or(RAMP.len() - 2);
+    let ((from_t, from), (to_t, to)) = (RAMP[stop], RAMP[stop + 1]);
+    let share = (t - from_t) / (to_t - from_t);
+    let channel = |i: usize| (from[i] as f64 + (to[i] as f64 - from[i] as f64) * share).round() as u8;
+    [channel(0), channel(1), channel(2), (120.0 + 135.0 * t).round() as u8]
+}
+
+// Rendered tiles on disk in dir/key/z/x/y.png. The key covers the filter and the data,
+// so a new activity moves the heatmap to a new key instead of invalidating tiles.
+pub struct TileCache {
+    dir: PathBuf,
+}
+
+impl TileCache {
+    pub fn from_settings(settings: &HeatmapSettings) -> TileCache {
+        TileCache { dir: PathBuf::from(&settings.cache_dir) }
+    }
+
+    pub fn path_for(&self, key: &str, tile: &Tile) -> PathBuf {
+        self.dir.join(key).join(tile.z.to_string()).join(tile.x.to_string()).join(format!("{}.png", tile.y))
+    }
+
+    pub fn get(&self, key: &str, tile: &Tile) -> Option<Vec<u8>> {
+        fs::read(self.path_for(key, tile)).ok()
+    }
+
+    // Written next to its place and renamed, like the blobs
+    pub fn put(&self, key: &str, tile: &Tile, bytes: &[u8]) -> std::io::Result<()> {
+        let path = self.path_for(key, tile);
+        let dir = path.parent().expect("Tile paths always have a parent");
+        fs::create_dir_all(dir)?;
+        let tmp_path = dir.join(format!("{}.png.tmp", tile.y));
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(bytes)?;
+        fs::rename(&tmp_path, &path)
+    }
+}
+
+// `version` is the count and the newest id of the activities on the heatmap
+pub fn cache_key(filter: &HeatmapFilter, version: (i64, Option<i64>)) -> String {
+    let mut sport_types = filter.sport_types.clone();
+    sport_types.sort();
+    let description = format!(
+        "{:?}|{}|{:?}|{:?}|{}|{:?}",
+        filter.athlete_id,
+        sport_types.join(","),
+        filter.after.map(|date| date.timestamp()),
+        filter.before.map(|date| date.timestamp()),
+        version.0,
+        version.1
+    );
+    content_hash(description.as_bytes())[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_projection() {
+        assert!(Tile::new(0, 0, 0).is_some());
+        assert!(Tile::new(2, 4, 0).is_none());
+        assert!(Tile::new(2, 0, 4).is_none());
+
+        // Null island is where the four tiles of zoom 1 meet
+        let tile = Tile::new(1, 1, 1).unwrap();
+        let (x, y) = tile.pixel(0.0, 0.0);
+        assert!(x.abs() < 1e-9 && y.abs() < 1e-9);
+
+        // Brussels at zoom 12
+        let tile = Tile::new(12, 2097, 1374).unwrap();
+        let (x, y) = tile.pixel(50.8467, 4.3525);
+        assert!((0.0..256.0).contains(&x) && (0.0..256.0).contains(&y));
+        let bounds = tile.bounds();
+        assert!(bounds.min_lat < 50.8467 && 50.8467 < bounds.max_lat);
+        assert!(bounds.min_lng < 4.3525 && 4.3525 < bounds.max_lng);
+        let (x, y) = tile.pixel(bounds.max_lat, bounds.min_lng);
+        assert!(x.abs() < 1e-6 && y.abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_track_bounds() {
+        let bounds = track_bounds(&[[50.0, 4.0], [50.5, 3.5], [49.8, 4.2]]).unwrap();
+        assert_eq!(bounds, Bounds { min_lat: 49.8, min_lng: 3.5, max_lat: 50.5, max_lng: 4.2 });
+        assert!(track_bounds(&[]).is_none());
+    }
+
+    #[test]
+    fn test_density() {
+        let tile = Tile::new(12, 2097, 1374).unwrap();
+        let bounds = tile.bounds();
+        let middle = (bounds.min_lat + bounds.max_lat) / 2.0;
+        // West to east across the whole tile and further, twice in the same activity
+        let across = [[middle, bounds.min_lng - 1.0], [middle, bounds.max_lng + 1.0], [middle, bounds.min_lng - 1.0]];
+
+        let mut density = Density::new(tile);
+        density.add(&across);
+        density.add(&across[..2]);
+        let row = tile.pixel(middle, 0.0).1 as usize * TILE_SIZE;
+        assert!(density.counts[row..row + TILE_SIZE].iter().all(|count| *count == 2));
+        assert_eq!(density.counts.iter().filter(|count| **count > 0).count(), TILE_SIZE);
+
+        // Somewhere else entirely
+        density.add(&[[0.0, 0.0], [1.0, 1.0]]);
+        assert_eq!(density.counts.iter().map(|count| *count as usize).sum::<usize>(), 2 * TILE_SIZE);
+
+        let png = density.to_png().unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+    }
+
+    #[test]
+    fn test_color() {
+        assert_eq!(color(0), [0, 0, 0, 0]);
+        assert!(color(1)[3] > 0);
+        assert!(color(10)[1] > color(1)[1]);
+        assert_eq!(color(1000), color(50));
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let filter = HeatmapFilter { sport_types: vec!["Run".to_string(), "Ride".to_string()], ..Default::default() };
+        let reordered = HeatmapFilter { sport_types: vec!["Ride".to_string(), "Run".to_string()], ..Default::default() };
+        assert_eq!(cache_key(&filter, (3, Some(12))), cache_key(&reordered, (3, Some(12))));
+        assert_ne!(cache_key(&filter, (3, Some(12))), cache_key(&filter, (4, Some(13))));
+        assert_ne!(cache_key(&filter, (3, Some(12))), cache_key(&HeatmapFilter::default(), (3, Some(12))));
+    }
+}
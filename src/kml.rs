@@ -0,0 +1,141 @@
+use crate::models::activity::ActivityRow;
+use chrono::Datelike;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+
+// KML colors are aabbggrr
+fn sport_color(sport_type: &str) -> &'static str {
+    match sport_type {
+        sport if sport.contains("Ride") => "ffd18b2b",
+        sport if sport.contains("Run") => "ff3c14dc",
+        "Hike" | "Walk" => "ff32a03c",
+        sport if sport.contains("Swim") => "ffd0c000",
+        sport if sport.contains("Ski") || sport.contains("Snowboard") => "ffe6a0a0",
+        _ => "ff008cff",
+    }
+}
+
+fn style_id(sport_type: &str) -> String {
+    let sport: String = sport_type.chars().filter(char::is_ascii_alphanumeric).collect();
+    format!("sport-{}", sport)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn duration(seconds: i32) -> String {
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+fn description(activity: &ActivityRow) -> String {
+    let mut lines = vec![
+        activity.start_date.format("%Y-%m-%d %H:%M UTC").to_string(),
+        format!("Distance: {:.2} km", activity.distance / 1000.0),
+        format!("Moving time: {}", duration(activity.moving_time)),
+    ];
+    if let Some(elevation) = activity.total_elevation_gain {
+        lines.push(format!("Elevation gain: {:.0} m", elevation));
+    }
+    lines.push(format!("<a href=\"https://www.strava.com/activities/{}\">On strava</a>", activity.id));
+    lines.join("<br/>")
+}
+
+// An activity and its track
+type Placemark<'a> = (&'a ActivityRow, Vec<[f64; 2]>);
+
+// A folder per year with a line per activity, colored by sport type. Activities without
+// a map are left out.
+pub fn document(activities: &[ActivityRow]) -> String {
+    let mut years: BTreeMap<i32, Vec<Placemark>> = BTreeMap::new();
+    for activity in activities {
+        if let Some((_, points)) = activity.track() {
+            years.entry(activity.start_date.year()).or_default().push((activity, points));
+        }
+    }
+    let sport_types: BTreeSet<&str> = years
+        .values()
+        .flatten()
+        .map(|(activity, _)| activity.sport_type.as_deref().unwrap_or_default())
+        .collect();
+
+    let mut kml = String::new();
+    kml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    kml.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n<name>Strava activities</name>\n");
+    for sport_type in sport_types {
+        let _ = writeln!(
+            kml,
+            "<Style id=\"{}\"><LineStyle><color>{}</color><width>3</width></LineStyle></Style>",
+            style_id(sport_type),
+            sport_color(sport_type)
+        );
+    }
+    for (year, placemarks) in years {
+        let _ = writeln!(kml, "<Folder>\n<name>{}</name>", year);
+        for (activity, points) in placemarks {
+            let coordinates: Vec<String> = points.iter().map(|[lat, lng]| format!("{},{}", lng, lat)).collect();
+            let _ = writeln!(
+                kml,
+                "<Placemark>\n<name>{}</name>\n<description><![CDATA[{}]]></description>\n<styleUrl>#{}</styleUrl>\n\
+                 <LineString><tessellate>1</tessellate><coordinates>{}</coordinates></LineString>\n</Placemark>",
+                escape(&activity.name),
+                description(activity),
+                style_id(activity.sport_type.as_deref().unwrap_or_default()),
+                coordinates.join(" ")
+            );
+        }
+        kml.push_str("</Folder>\n");
+    }
+    kml.push_str("</Document>\n</kml>\n");
+    kml
+}
+
+// Zipped KML, Google Earth opens the doc.kml in it
+pub fn kmz(kml: &str) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("doc.kml", SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated))?;
+    zip.write_all(kml.as_bytes())?;
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::tests::activity_row;
+    use std::io::Read;
+
+    fn fixtures() -> Vec<ActivityRow> {
+        let mut ride = activity_row(1, "2023-06-01T09:00:00Z", "Ride", 42200.0);
+        ride.name = "Tom & Jerry <3".to_string();
+        ride.summary_polyline = Some("_p~iF~ps|U_ulLnnqC".to_string());
+        let mut run = activity_row(2, "2024-01-01T09:00:00Z", "Run", 10000.0);
+        run.track_polyline = Some("_p~iF~ps|U_ulLnnqC_mqNvxq`@".to_string());
+        let indoor = activity_row(3, "2024-01-02T09:00:00Z", "VirtualRide", 20000.0);
+        vec![ride, run, indoor]
+    }
+
+    #[test]
+    fn test_document() {
+        let kml = document(&fixtures());
+        assert_eq!(kml.matches("<Folder>").count(), 2);
+        assert_eq!(kml.matches("<Placemark>").count(), 2);
+        assert!(kml.find("<name>2023</name>").unwrap() < kml.find("<name>2024</name>").unwrap());
+        assert!(kml.contains("<Style id=\"sport-Ride\"><LineStyle><color>ffd18b2b</color>"));
+        assert!(!kml.contains("sport-VirtualRide"));
+        assert!(kml.contains("<name>Tom &amp; Jerry &lt;3</name>"));
+        assert!(kml.contains("Distance: 42.20 km<br/>Moving time: 1:00:00"));
+        assert!(kml.contains("<coordinates>-120.2,38.5 -120.95,40.7 -126.453,43.252</coordinates>"));
+    }
+
+    #[test]
+    fn test_kmz() {
+        let kml = document(&fixtures());
+        let bytes = kmz(&kml).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut unzipped = String::new();
+        archive.by_name("doc.kml").unwrap().read_to_string(&mut unzipped).unwrap();
+        assert_eq!(unzipped, kml);
+    }
+}
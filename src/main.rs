@@ -1,5 +1,7 @@
 mod strava_endpoints;
+mod activity_endpoints;
 mod settings;
+mod secrets;
 
 mod strava;
 
@@ -7,25 +9,57 @@ mod schema;
 mod db_connection;
 
 mod models;
-
+mod analysis;
+mod cli;
+mod crypto;
+mod storage;
+mod stats;
+mod heatmap;
+mod polyline;
+mod segments;
+mod social;
+mod clubs;
+mod gear;
+mod geojson;
+mod kml;
+mod export;
+mod sync;
+
+use crate::cli::{Cli, Command};
 use crate::db_connection::establish_connection;
+use crate::settings::Settings;
 
-use dotenv::dotenv;
 use axum::Router;
+use clap::Parser;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    dotenv().ok(); //TODO: Replace by the settings thingy
+    let cli = Cli::parse();
+    let settings = match Settings::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match cli.command {
+        None | Some(Command::Serve) => serve(&settings).await,
+        Some(command) => cli::run(command, &settings).await,
+    }
+}
 
-    let db_conn = establish_connection();
+async fn serve(settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+    let db_conn = establish_connection(&settings.database);
 
     // region: --- APP
     // build our application with a single route
     let app = Router::new()
-        .merge(strava_endpoints::strava_router(db_conn.clone()));
+        .merge(strava_endpoints::strava_router(db_conn.clone(), settings))
+        .merge(activity_endpoints::activity_router(db_conn.clone(), settings));
 
-    // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3007")
+    // run our app with hyper, listening on the configured address
+    let listener = tokio::net::TcpListener::bind((settings.server.host.as_str(), settings.server.port))
         .await
         .unwrap();
 
@@ -39,8 +73,6 @@ use axum::http::StatusCode;
 use axum::Json;
 use axum::response::{IntoResponse, Response};
 use serde::Serialize;
-use crate::models::athlete::{create_athlete, AthleteRow, NewAthleteRow};
-use crate::schema::athletes::dsl::athletes;
 
 pub enum ApiResponse<T> {
     OK,
@@ -59,11 +91,20 @@ where
     }
 }
 
+#[derive(Debug)]
 pub struct ApiError {
     pub status_code: StatusCode,
     pub message: String,
 }
 
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.status_code)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         (self.status_code, Json(self.message)).into_response()
@@ -0,0 +1,58 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use crate::ApiError;
+use crate::models::effort::NewBestEffortRow;
+use crate::models::heatmap::NewActivityBoundsRow;
+use crate::models::power_curve::NewPowerCurveRow;
+use crate::models::training_load::NewActivityLoadRow;
+use crate::models::zones::NewZoneTimeRow;
+
+// Everything the analysis derives from the streams of one activity
+pub struct AnalysisRows {
+    pub best_efforts: Vec<NewBestEffortRow>,
+    pub power_curve: Vec<NewPowerCurveRow>,
+    // None without the thresholds or streams to compute it
+    pub load: Option<NewActivityLoadRow>,
+    pub zone_times: Vec<NewZoneTimeRow>,
+    // None without gps
+    pub bounds: Option<NewActivityBoundsRow>,
+    // The latlng stream, encoded
+    pub track_polyline: Option<String>,
+}
+
+// Replace the results of an activity and mark it analyzed, in one go so an activity is
+// never left half done
+pub async fn save_analysis(conn: Object, activity: i64, rows: AnalysisRows) -> Result<(), ApiError> {
+    use crate::schema::{activities, activity_bounds, activity_loads, activity_zone_times, best_efforts, power_curve_points};
+
+    conn.interact(move |conn| {
+        conn.transaction(|conn| {
+            diesel::delete(best_efforts::table.filter(best_efforts::activity_id.eq(activity))).execute(conn)?;
+            diesel::insert_into(best_efforts::table).values(&rows.best_efforts).execute(conn)?;
+            diesel::delete(power_curve_points::table.filter(power_curve_points::activity_id.eq(activity))).execute(conn)?;
+            diesel::insert_into(power_curve_points::table).values(&rows.power_curve).execute(conn)?;
+            diesel::delete(activity_loads::table.find(activity)).execute(conn)?;
+            if let Some(load) = &rows.load {
+                diesel::insert_into(activity_loads::table).values(load).execute(conn)?;
+            }
+            diesel::delete(activity_zone_times::table.filter(activity_zone_times::activity_id.eq(activity))).execute(conn)?;
+            diesel::insert_into(activity_zone_times::table).values(&rows.zone_times).execute(conn)?;
+            diesel::delete(activity_bounds::table.find(activity)).execute(conn)?;
+            if let Some(bounds) = &rows.bounds {
+                diesel::insert_into(activity_bounds::table).values(bounds).execute(conn)?;
+            }
+            diesel::update(activities::table.find(activity))
+                .set((
+                    activities::track_polyline.eq(&rows.track_polyline),
+                    activities::analyzed_at.eq(Some(Utc::now().naive_utc())),
+                ))
+                .execute(conn)?;
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_: diesel::result::Error| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
@@ -1,11 +1,8 @@
-use chrono::{DateTime, NaiveDateTime, Utc};
-use diesel::pg::Pg;
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
-use diesel::result::DatabaseErrorKind;
-use crate::schema::athletes::dsl::athletes;
 use axum::http::StatusCode;
-use deadpool_diesel::postgres::{Object, Pool}; // Import the Pool type
-use crate::{ApiError, ApiResponse};
+use deadpool_diesel::postgres::Object;
+use crate::ApiError;
 
 #[derive(Insertable)]
 #[diesel(table_name=crate::schema::athletes)]
@@ -19,19 +16,6 @@ pub struct NewAthleteRow {
 }
 
 
-#[derive(Queryable, Selectable)]
-#[diesel(table_name=crate::schema::athletes)]
-#[diesel(check_for_backend(diesel::pg::Pg))]
-pub struct AthleteRow {
-    pub id: i64,
-    pub username: Option<String>,
-    pub firstname: Option<String>,
-    pub lastname: Option<String>,
-    pub created_at: NaiveDateTime,
-    pub updated_at: Option<NaiveDateTime>,
-    pub another_column: Option<String>,
-}
-
 pub async fn create_athlete(
     conn: Object,
     user_id: i64,
@@ -39,7 +23,6 @@ pub async fn create_athlete(
     first_name: String,
     last_name: String,
 ) -> Result<(), ApiError> {
-    use crate::schema::athletes;
     use crate::schema::athletes::dsl::*;
     println!("heheheh");
     let new_athlete = NewAthleteRow {
@@ -62,5 +45,5 @@ pub async fn create_athlete(
     .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
     .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })?;
 
-    return Ok(())
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,122 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use crate::ApiError;
+
+#[derive(Insertable)]
+#[diesel(table_name=crate::schema::blobs)]
+pub struct NewBlobRow {
+    pub hash: String,
+    pub size_bytes: i64,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=crate::schema::blob_refs)]
+pub struct NewBlobRefRow {
+    pub hash: String,
+    pub owner: String,
+    pub created_at: NaiveDateTime,
+}
+
+// Point `blob_owner` (e.g. "activity:123:streams") at the blob, in place of whatever it
+// referenced before. Delete and insert share a transaction so gc never sees the owner
+// without a reference. Referencing a blob again restarts its gc grace period.
+pub async fn replace_blob_ref(
+    conn: Object,
+    blob_owner: String,
+    blob_hash: String,
+    blob_size: usize,
+) -> Result<(), ApiError> {
+    use crate::schema::{blob_refs, blobs};
+
+    let new_blob = NewBlobRow {
+        hash: blob_hash.clone(),
+        size_bytes: blob_size as i64,
+        created_at: Utc::now().naive_utc(),
+    };
+    let new_ref = NewBlobRefRow {
+        hash: blob_hash,
+        owner: blob_owner,
+        created_at: Utc::now().naive_utc(),
+    };
+    conn.interact(move |conn| {
+        conn.transaction(|conn| {
+            diesel::delete(blob_refs::table.filter(blob_refs::owner.eq(&new_ref.owner))).execute(conn)?;
+            diesel::insert_into(blobs::table)
+                .values(&new_blob)
+                .on_conflict(blobs::hash)
+                .do_update()
+                .set(blobs::created_at.eq(new_blob.created_at))
+                .execute(conn)?;
+            diesel::insert_into(blob_refs::table).values(&new_ref).execute(conn)
+        })
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })?;
+
+    Ok(())
+}
+
+pub async fn has_blob_ref(conn: Object, blob_owner: String) -> Result<bool, ApiError> {
+    use crate::schema::blob_refs::dsl::*;
+    use diesel::dsl::exists;
+
+    conn.interact(move |conn| {
+        diesel::select(exists(blob_refs.filter(owner.eq(blob_owner)))).get_result(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+// Hash of the blob the owner references, owners like "activity:123:streams" hold one.
+// Rows left over from before re-syncs replaced the old reference lose to the newest.
+pub async fn find_blob_ref(conn: Object, blob_owner: String) -> Result<Option<String>, ApiError> {
+    use crate::schema::blob_refs::dsl::*;
+
+    conn.interact(move |conn| {
+        blob_refs.filter(owner.eq(blob_owner)).order(created_at.desc()).select(hash).first(conn).optional()
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+// find_blob_ref for many owners in one query, owners without a blob are left out
+pub async fn find_blob_refs(conn: Object, owners: Vec<String>) -> Result<Vec<String>, ApiError> {
+    use crate::schema::blob_refs::dsl::*;
+
+    conn.interact(move |conn| {
+        blob_refs
+            .filter(owner.eq_any(owners))
+            .distinct_on(owner)
+            .order((owner, created_at.desc()))
+            .select(hash)
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+// Remove the rows of blobs nobody references anymore and return their hashes so the
+// caller can delete the files. Blobs referenced after `older_than` are kept.
+pub async fn take_unreferenced_blobs(conn: Object, older_than: NaiveDateTime) -> Result<Vec<String>, ApiError> {
+    use crate::schema::{blob_refs, blobs};
+    use diesel::dsl::{exists, not};
+
+    conn.interact(move |conn| {
+        let orphans = blobs::table.filter(blobs::created_at.lt(older_than)).filter(not(exists(
+            blob_refs::table.filter(blob_refs::hash.eq(blobs::hash)),
+        )));
+        diesel::delete(orphans)
+            .returning(blobs::hash)
+            .get_results::<String>(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
@@ -0,0 +1,343 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use serde::Serialize;
+use crate::ApiError;
+use crate::storage::content_hash;
+use crate::strava::parsers::{Club, ClubActivity, ClubMember};
+
+#[derive(Insertable, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name=crate::schema::clubs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ClubRow {
+    pub id: i64,
+    pub name: String,
+    pub sport_type: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub private: bool,
+    pub member_count: Option<i32>,
+    pub url: Option<String>,
+    pub description: Option<String>,
+    pub club_type: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+impl ClubRow {
+    pub fn from_club(club: &Club) -> ClubRow {
+        ClubRow {
+            id: club.id,
+            name: club.name.clone(),
+            sport_type: club.sport_type.clone(),
+            city: club.city.clone(),
+            state: club.state.clone(),
+            country: club.country.clone(),
+            private: club.private,
+            member_count: club.member_count,
+            url: club.url.clone(),
+            description: club.description.clone().filter(|description| !description.is_empty()),
+            club_type: club.club_type.clone(),
+            updated_at: Utc::now().naive_utc(),
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name=crate::schema::club_members)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ClubMemberRow {
+    pub club_id: i64,
+    pub firstname: String,
+    pub lastname: String,
+    pub membership: Option<String>,
+    pub admin: bool,
+    pub owner: bool,
+}
+
+impl ClubMemberRow {
+    pub fn from_member(club_id: i64, member: &ClubMember) -> ClubMemberRow {
+        ClubMemberRow {
+            club_id,
+            firstname: member.firstname.clone().unwrap_or_default(),
+            lastname: member.lastname.clone().unwrap_or_default(),
+            membership: member.membership.clone(),
+            admin: member.admin,
+            owner: member.owner,
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Selectable, Serialize, Clone, Debug)]
+#[diesel(table_name=crate::schema::club_activities)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ClubActivityRow {
+    pub key: String,
+    pub club_id: i64,
+    pub firstname: String,
+    pub lastname: String,
+    pub name: String,
+    pub sport_type: Option<String>,
+    pub distance: f32,
+    pub moving_time: i32,
+    pub elapsed_time: i32,
+    pub total_elevation_gain: f32,
+    pub workout_type: Option<i32>,
+    pub first_seen_at: NaiveDateTime,
+}
+
+impl ClubActivityRow {
+    // The key hashes the athlete, sport and metrics strava sends, so the same activity seen
+    // on a later sync gets the same key. The name and workout type can be edited and are
+    // left out, the row keeps the ones first seen. Two activities only collide when they
+    // match to the second and the decimeter.
+    pub fn from_activity(club_id: i64, activity: &ClubActivity) -> ClubActivityRow {
+        let firstname = activity.athlete.firstname.clone().unwrap_or_default();
+        let lastname = activity.athlete.lastname.clone().unwrap_or_default();
+        let fields = serde_json::json!([
+            club_id,
+            firstname,
+            lastname,
+            activity.sport_type,
+            activity.distance,
+            activity.moving_time,
+            activity.elapsed_time,
+            activity.total_elevation_gain,
+        ]);
+        ClubActivityRow {
+            key: content_hash(fields.to_string().as_bytes()),
+            club_id,
+            firstname,
+            lastname,
+            name: activity.name.clone(),
+            sport_type: activity.sport_type.clone(),
+            distance: activity.distance,
+            moving_time: activity.moving_time,
+            elapsed_time: activity.elapsed_time,
+            total_elevation_gain: activity.total_elevation_gain,
+            workout_type: activity.workout_type,
+            first_seen_at: Utc::now().naive_utc(),
+        }
+    }
+}
+
+// The club list leaves out the description and club type, those stay as the club
+// detail left them
+pub async fn replace_athlete_clubs(conn: Object, athlete: i64, rows: Vec<ClubRow>) -> Result<(), ApiError> {
+    use crate::schema::{athlete_clubs, clubs};
+
+    let memberships: Vec<_> = rows
+        .iter()
+        .map(|club| (athlete_clubs::athlete_id.eq(athlete), athlete_clubs::club_id.eq(club.id)))
+        .collect();
+    conn.interact(move |conn| {
+        conn.transaction(|conn| {
+            diesel::insert_into(clubs::table)
+                .values(&rows)
+                .on_conflict(clubs::id)
+                .do_update()
+                .set((
+                    clubs::name.eq(excluded(clubs::name)),
+                    clubs::sport_type.eq(excluded(clubs::sport_type)),
+                    clubs::city.eq(excluded(clubs::city)),
+                    clubs::state.eq(excluded(clubs::state)),
+                    clubs::country.eq(excluded(clubs::country)),
+                    clubs::private.eq(excluded(clubs::private)),
+                    clubs::member_count.eq(excluded(clubs::member_count)),
+                    clubs::url.eq(excluded(clubs::url)),
+                    clubs::updated_at.eq(excluded(clubs::updated_at)),
+                ))
+                .execute(conn)?;
+            diesel::delete(athlete_clubs::table.filter(athlete_clubs::athlete_id.eq(athlete))).execute(conn)?;
+            diesel::insert_into(athlete_clubs::table).values(&memberships).on_conflict_do_nothing().execute(conn)
+        })
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })?;
+    Ok(())
+}
+
+pub async fn save_club_detail(conn: Object, row: ClubRow) -> Result<usize, ApiError> {
+    use crate::schema::clubs::dsl::*;
+
+    conn.interact(move |conn| {
+        diesel::insert_into(clubs)
+            .values(&row)
+            .on_conflict(id)
+            .do_update()
+            .set((
+                name.eq(excluded(name)),
+                sport_type.eq(excluded(sport_type)),
+                city.eq(excluded(city)),
+                state.eq(excluded(state)),
+                country.eq(excluded(country)),
+                private.eq(excluded(private)),
+                member_count.eq(excluded(member_count)),
+                url.eq(excluded(url)),
+                description.eq(excluded(description)),
+                club_type.eq(excluded(club_type)),
+                updated_at.eq(excluded(updated_at)),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
+
+// The clubs out of `ids` whose members were fetched longest ago, never fetched first
+pub async fn clubs_due_for_members(conn: Object, ids: Vec<i64>, limit: i64) -> Result<Vec<i64>, ApiError> {
+    use crate::schema::clubs::dsl::*;
+
+    conn.interact(move |conn| {
+        clubs
+            .filter(id.eq_any(ids))
+            .order((members_synced_at.asc().nulls_first(), id.asc()))
+            .limit(limit)
+            .select(id)
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+// Names can repeat in a club, the first one is kept
+pub async fn replace_club_members(conn: Object, club: i64, rows: Vec<ClubMemberRow>) -> Result<(), ApiError> {
+    use crate::schema::{club_members, clubs};
+
+    conn.interact(move |conn| {
+        conn.transaction(|conn| {
+            diesel::delete(club_members::table.filter(club_members::club_id.eq(club))).execute(conn)?;
+            diesel::insert_into(club_members::table).values(&rows).on_conflict_do_nothing().execute(conn)?;
+            diesel::update(clubs::table.find(club))
+                .set(clubs::members_synced_at.eq(Some(Utc::now().naive_utc())))
+                .execute(conn)
+        })
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })?;
+    Ok(())
+}
+
+// Returns how many were new, activities seen before keep their first_seen_at
+pub async fn insert_club_activities(conn: Object, rows: Vec<ClubActivityRow>) -> Result<usize, ApiError> {
+    use crate::schema::club_activities::dsl::*;
+
+    conn.interact(move |conn| diesel::insert_into(club_activities).values(&rows).on_conflict_do_nothing().execute(conn))
+        .await
+        .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+        .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
+
+pub async fn get_athlete_clubs(conn: Object, athlete: i64) -> Result<Vec<ClubRow>, ApiError> {
+    use crate::schema::{athlete_clubs, clubs};
+
+    conn.interact(move |conn| {
+        clubs::table
+            .inner_join(athlete_clubs::table)
+            .filter(athlete_clubs::athlete_id.eq(athlete))
+            .order(clubs::name.asc())
+            .select(ClubRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_club(conn: Object, club: i64) -> Result<Option<ClubRow>, ApiError> {
+    use crate::schema::clubs::dsl::*;
+
+    conn.interact(move |conn| {
+        clubs
+            .find(club)
+            .select(ClubRow::as_select())
+            .first(conn)
+            .optional()
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_club_members(conn: Object, club: i64) -> Result<Vec<ClubMemberRow>, ApiError> {
+    use crate::schema::club_members::dsl::*;
+
+    conn.interact(move |conn| {
+        club_members
+            .filter(club_id.eq(club))
+            .order((owner.desc(), admin.desc(), firstname.asc(), lastname.asc()))
+            .select(ClubMemberRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_club_activities(
+    conn: Object,
+    club: i64,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) -> Result<Vec<ClubActivityRow>, ApiError> {
+    use crate::schema::club_activities::dsl::*;
+
+    conn.interact(move |conn| {
+        let mut query = club_activities
+            .filter(club_id.eq(club))
+            .select(ClubActivityRow::as_select())
+            .into_boxed();
+        if let Some(after) = after {
+            query = query.filter(first_seen_at.ge(after.naive_utc()));
+        }
+        if let Some(before) = before {
+            query = query.filter(first_seen_at.lt(before.naive_utc()));
+        }
+        query.order(first_seen_at.asc()).load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(name: &str, distance: f32) -> ClubActivity {
+        serde_json::from_value(serde_json::json!({
+            "athlete": { "firstname": "Peter", "lastname": "S." },
+            "name": name,
+            "distance": distance,
+            "moving_time": 577,
+            "elapsed_time": 635,
+            "total_elevation_gain": 8.8,
+            "sport_type": "Ride",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_club_activity_key() {
+        let key = ClubActivityRow::from_activity(1, &activity("Lunch ride", 2641.7)).key;
+        assert_eq!(ClubActivityRow::from_activity(1, &activity("Lunch ride", 2641.7)).key, key);
+        assert_ne!(ClubActivityRow::from_activity(2, &activity("Lunch ride", 2641.7)).key, key);
+        assert_ne!(ClubActivityRow::from_activity(1, &activity("Lunch ride", 2641.8)).key, key);
+    }
+
+    #[test]
+    fn test_club_activity_key_survives_edits() {
+        let key = ClubActivityRow::from_activity(1, &activity("Lunch ride", 2641.7)).key;
+        assert_eq!(ClubActivityRow::from_activity(1, &activity("Evening ride", 2641.7)).key, key);
+
+        let mut race = activity("Lunch ride", 2641.7);
+        race.workout_type = Some(11);
+        assert_eq!(ClubActivityRow::from_activity(1, &race).key, key);
+    }
+}
@@ -0,0 +1,57 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use serde::Serialize;
+use crate::ApiError;
+
+#[derive(Insertable)]
+#[diesel(table_name=crate::schema::best_efforts)]
+pub struct NewBestEffortRow {
+    pub activity_id: i64,
+    pub kind: String,
+    pub name: String,
+    pub athlete_id: i64,
+    pub sport_type: Option<String>,
+    pub value: f32,
+    pub start_offset: i32,
+    pub start_date: DateTime<Utc>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Serialize, Clone, Debug)]
+#[diesel(table_name=crate::schema::best_efforts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BestEffortRow {
+    pub activity_id: i64,
+    pub kind: String,
+    pub name: String,
+    pub athlete_id: i64,
+    pub sport_type: Option<String>,
+    pub value: f32,
+    pub start_offset: i32,
+    pub start_date: DateTime<Utc>,
+    pub created_at: NaiveDateTime,
+}
+
+pub async fn get_athlete_efforts(
+    conn: Object,
+    athlete: i64,
+    sport: Option<String>,
+) -> Result<Vec<BestEffortRow>, ApiError> {
+    use crate::schema::best_efforts::dsl::*;
+
+    conn.interact(move |conn| {
+        let mut query = best_efforts
+            .filter(athlete_id.eq(athlete))
+            .select(BestEffortRow::as_select())
+            .into_boxed();
+        if let Some(sport) = sport {
+            query = query.filter(sport_type.eq(sport));
+        }
+        query.order(start_date.asc()).load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
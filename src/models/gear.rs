@@ -0,0 +1,242 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use serde::Serialize;
+use crate::ApiError;
+use crate::strava::parsers::Gear;
+
+#[derive(Insertable, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name=crate::schema::gear)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct GearRow {
+    pub id: String,
+    pub athlete_id: i64,
+    pub kind: String,
+    pub name: Option<String>,
+    pub brand_name: Option<String>,
+    pub model_name: Option<String>,
+    pub description: Option<String>,
+    pub is_primary: bool,
+    pub retired: bool,
+    pub distance: f32,
+    pub updated_at: NaiveDateTime,
+}
+
+impl GearRow {
+    pub fn from_gear(athlete_id: i64, gear: Gear) -> GearRow {
+        GearRow {
+            kind: gear.kind().to_string(),
+            id: gear.id,
+            athlete_id,
+            name: gear.name,
+            brand_name: gear.brand_name,
+            model_name: gear.model_name,
+            description: gear.description,
+            is_primary: gear.primary,
+            retired: gear.retired,
+            distance: gear.distance,
+            updated_at: Utc::now().naive_utc(),
+        }
+    }
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name=crate::schema::gear_components)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewComponentRow {
+    pub gear_id: String,
+    pub kind: String,
+    pub name: Option<String>,
+    pub installed_at: NaiveDate,
+    pub removed_at: Option<NaiveDate>,
+    pub service_distance: Option<f32>,
+    pub service_time: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Serialize, Clone, Debug, PartialEq)]
+#[diesel(table_name=crate::schema::gear_components)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ComponentRow {
+    pub id: i32,
+    pub gear_id: String,
+    pub kind: String,
+    pub name: Option<String>,
+    pub installed_at: NaiveDate,
+    pub removed_at: Option<NaiveDate>,
+    pub service_distance: Option<f32>,
+    pub service_time: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+// What an activity puts on its gear
+#[derive(Queryable, Selectable, Clone)]
+#[diesel(table_name=crate::schema::activities)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct GearActivityRow {
+    #[diesel(select_expression = crate::schema::activities::gear_id.assume_not_null())]
+    pub gear_id: String,
+    pub start_date: DateTime<Utc>,
+    pub distance: f32,
+    pub moving_time: i32,
+}
+
+pub async fn upsert_gear(conn: Object, row: GearRow) -> Result<usize, ApiError> {
+    use crate::schema::gear::dsl::*;
+
+    conn.interact(move |conn| {
+        diesel::insert_into(gear)
+            .values(&row)
+            .on_conflict(id)
+            .do_update()
+            .set((
+                athlete_id.eq(excluded(athlete_id)),
+                kind.eq(excluded(kind)),
+                name.eq(excluded(name)),
+                brand_name.eq(excluded(brand_name)),
+                model_name.eq(excluded(model_name)),
+                description.eq(excluded(description)),
+                is_primary.eq(excluded(is_primary)),
+                retired.eq(excluded(retired)),
+                distance.eq(excluded(distance)),
+                updated_at.eq(excluded(updated_at)),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
+
+pub async fn get_athlete_gear(conn: Object, athlete: i64) -> Result<Vec<GearRow>, ApiError> {
+    use crate::schema::gear::dsl::*;
+
+    conn.interact(move |conn| {
+        gear.filter(athlete_id.eq(athlete))
+            .order((retired.asc(), kind.asc(), id.asc()))
+            .select(GearRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_gear(conn: Object, gear_key: String) -> Result<Option<GearRow>, ApiError> {
+    use crate::schema::gear::dsl::*;
+
+    conn.interact(move |conn| {
+        gear
+            .find(gear_key)
+            .select(GearRow::as_select())
+            .first(conn)
+            .optional()
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+// Gear the athlete's activities were done with, strava leaves retired gear out of the athlete
+pub async fn activity_gear_ids(conn: Object, athlete: i64) -> Result<Vec<String>, ApiError> {
+    use crate::schema::activities::dsl::*;
+
+    conn.interact(move |conn| {
+        activities
+            .filter(athlete_id.eq(athlete))
+            .filter(gear_id.is_not_null())
+            .select(gear_id.assume_not_null())
+            .distinct()
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_gear_activities(conn: Object, athlete: i64) -> Result<Vec<GearActivityRow>, ApiError> {
+    use crate::schema::activities::dsl::*;
+
+    conn.interact(move |conn| {
+        activities
+            .filter(athlete_id.eq(athlete))
+            .filter(gear_id.is_not_null())
+            .order(start_date.asc())
+            .select(GearActivityRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_components(conn: Object, gear_ids: Vec<String>) -> Result<Vec<ComponentRow>, ApiError> {
+    use crate::schema::gear_components::dsl::*;
+
+    conn.interact(move |conn| {
+        gear_components
+            .filter(gear_id.eq_any(gear_ids))
+            .order((gear_id.asc(), installed_at.asc(), id.asc()))
+            .select(ComponentRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_component(conn: Object, component: i32) -> Result<Option<ComponentRow>, ApiError> {
+    use crate::schema::gear_components::dsl::*;
+
+    conn.interact(move |conn| {
+        gear_components
+            .find(component)
+            .select(ComponentRow::as_select())
+            .first(conn)
+            .optional()
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn insert_component(conn: Object, row: NewComponentRow) -> Result<ComponentRow, ApiError> {
+    use crate::schema::gear_components::dsl::*;
+
+    conn.interact(move |conn| {
+        diesel::insert_into(gear_components)
+            .values(&row)
+            .returning(ComponentRow::as_returning())
+            .get_result(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
+
+// Replaces every field, None when the component doesn't exist
+pub async fn update_component(conn: Object, component: i32, row: NewComponentRow) -> Result<Option<ComponentRow>, ApiError> {
+    use crate::schema::gear_components::dsl::*;
+
+    conn.interact(move |conn| {
+        diesel::update(gear_components.find(component))
+            .set(&row)
+            .returning(ComponentRow::as_returning())
+            .get_result(conn)
+            .optional()
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
+
+pub async fn delete_component(conn: Object, component: i32) -> Result<usize, ApiError> {
+    use crate::schema::gear_components::dsl::*;
+
+    conn.interact(move |conn| diesel::delete(gear_components.find(component)).execute(conn))
+        .await
+        .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+        .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
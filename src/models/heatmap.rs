@@ -0,0 +1,83 @@
+use chrono::{DateTime, Utc};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use crate::ApiError;
+use crate::heatmap::Bounds;
+use crate::schema::activity_bounds;
+
+#[derive(Insertable)]
+#[diesel(table_name=crate::schema::activity_bounds)]
+pub struct NewActivityBoundsRow {
+    pub activity_id: i64,
+    pub athlete_id: i64,
+    pub sport_type: Option<String>,
+    pub start_date: DateTime<Utc>,
+    pub min_lat: f64,
+    pub min_lng: f64,
+    pub max_lat: f64,
+    pub max_lng: f64,
+}
+
+// Which activities end up on the heatmap
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeatmapFilter {
+    pub athlete_id: Option<i64>,
+    pub sport_types: Vec<String>,
+    pub after: Option<DateTime<Utc>>,
+    pub before: Option<DateTime<Utc>>,
+}
+
+fn filtered(filter: HeatmapFilter) -> activity_bounds::BoxedQuery<'static, Pg> {
+    use crate::schema::activity_bounds::dsl::*;
+
+    let mut query = activity_bounds.into_boxed();
+    if let Some(value) = filter.athlete_id {
+        query = query.filter(athlete_id.eq(value));
+    }
+    if !filter.sport_types.is_empty() {
+        query = query.filter(sport_type.eq_any(filter.sport_types));
+    }
+    if let Some(value) = filter.after {
+        query = query.filter(start_date.ge(value));
+    }
+    if let Some(value) = filter.before {
+        query = query.filter(start_date.lt(value));
+    }
+    query
+}
+
+// The activities passing through `bounds`, or at least whose own bounds overlap them
+pub async fn activities_in_bounds(conn: Object, filter: HeatmapFilter, bounds: Bounds) -> Result<Vec<i64>, ApiError> {
+    use crate::schema::activity_bounds::dsl::*;
+
+    conn.interact(move |conn| {
+        filtered(filter)
+            .filter(max_lat.ge(bounds.min_lat))
+            .filter(min_lat.le(bounds.max_lat))
+            .filter(max_lng.ge(bounds.min_lng))
+            .filter(min_lng.le(bounds.max_lng))
+            .select(activity_id)
+            .order(activity_id.asc())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+// Changes whenever an activity is added to or removed from the heatmap, cached tiles of
+// another version are stale
+pub async fn heatmap_version(conn: Object, filter: HeatmapFilter) -> Result<(i64, Option<i64>), ApiError> {
+    use crate::schema::activity_bounds::dsl::*;
+
+    conn.interact(move |conn| {
+        filtered(filter)
+            .select((diesel::dsl::count_star(), diesel::dsl::max(activity_id)))
+            .first(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
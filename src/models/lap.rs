@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use serde::Serialize;
+use crate::ApiError;
+use crate::strava::parsers::{Lap, Split};
+
+#[derive(Insertable, Queryable, Selectable, Serialize)]
+#[diesel(table_name=crate::schema::activity_laps)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct LapRow {
+    pub activity_id: i64,
+    pub lap_index: i32,
+    pub id: i64,
+    pub name: Option<String>,
+    pub elapsed_time: i32,
+    pub moving_time: i32,
+    pub start_date: DateTime<Utc>,
+    pub distance: f32,
+    pub start_index: Option<i32>,
+    pub end_index: Option<i32>,
+    pub total_elevation_gain: Option<f32>,
+    pub average_speed: Option<f32>,
+    pub max_speed: Option<f32>,
+    pub average_heartrate: Option<f32>,
+    pub max_heartrate: Option<f32>,
+    pub average_watts: Option<f32>,
+    pub average_cadence: Option<f32>,
+}
+
+impl LapRow {
+    pub fn from_lap(activity_id: i64, lap: &Lap) -> LapRow {
+        LapRow {
+            activity_id,
+            lap_index: lap.lap_index,
+            id: lap.id,
+            name: lap.name.clone(),
+            elapsed_time: lap.elapsed_time,
+            moving_time: lap.moving_time,
+            start_date: lap.start_date,
+            distance: lap.distance,
+            start_index: lap.start_index,
+            end_index: lap.end_index,
+            total_elevation_gain: lap.total_elevation_gain,
+            average_speed: lap.average_speed,
+            max_speed: lap.max_speed,
+            average_heartrate: lap.average_heartrate,
+            max_heartrate: lap.max_heartrate,
+            average_watts: lap.average_watts,
+            average_cadence: lap.average_cadence,
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Selectable, Serialize)]
+#[diesel(table_name=crate::schema::activity_splits)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SplitRow {
+    pub activity_id: i64,
+    pub units: String,
+    pub split: i32,
+    pub distance: f32,
+    pub elapsed_time: i32,
+    pub moving_time: i32,
+    pub elevation_difference: Option<f32>,
+    pub average_speed: Option<f32>,
+    pub average_grade_adjusted_speed: Option<f32>,
+    pub average_heartrate: Option<f32>,
+    pub pace_zone: Option<i32>,
+}
+
+impl SplitRow {
+    // `units` is "metric" or "standard"
+    pub fn from_split(activity_id: i64, units: &str, split: &Split) -> SplitRow {
+        SplitRow {
+            activity_id,
+            units: units.to_string(),
+            split: split.split,
+            distance: split.distance,
+            elapsed_time: split.elapsed_time,
+            moving_time: split.moving_time,
+            elevation_difference: split.elevation_difference,
+            average_speed: split.average_speed,
+            average_grade_adjusted_speed: split.average_grade_adjusted_speed,
+            average_heartrate: split.average_heartrate,
+            pace_zone: split.pace_zone,
+        }
+    }
+}
+
+// Swap the laps and splits of an activity for the ones in its detailed version
+pub async fn replace_laps(conn: Object, activity: i64, laps: Vec<LapRow>, splits: Vec<SplitRow>) -> Result<(), ApiError> {
+    use crate::schema::{activity_laps, activity_splits};
+
+    conn.interact(move |conn| {
+        conn.transaction(|conn| {
+            diesel::delete(activity_laps::table.filter(activity_laps::activity_id.eq(activity))).execute(conn)?;
+            diesel::delete(activity_splits::table.filter(activity_splits::activity_id.eq(activity))).execute(conn)?;
+            diesel::insert_into(activity_laps::table).values(&laps).execute(conn)?;
+            diesel::insert_into(activity_splits::table).values(&splits).execute(conn)
+        })
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })?;
+    Ok(())
+}
+
+pub async fn get_laps(conn: Object, activity: i64) -> Result<Vec<LapRow>, ApiError> {
+    use crate::schema::activity_laps::dsl::*;
+
+    conn.interact(move |conn| {
+        activity_laps
+            .filter(activity_id.eq(activity))
+            .order(lap_index.asc())
+            .select(LapRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_splits(conn: Object, activity: i64) -> Result<Vec<SplitRow>, ApiError> {
+    use crate::schema::activity_splits::dsl::*;
+
+    conn.interact(move |conn| {
+        activity_splits
+            .filter(activity_id.eq(activity))
+            .order((units.asc(), split.asc()))
+            .select(SplitRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
@@ -1 +1,17 @@
-pub mod athlete;
\ No newline at end of file
+pub mod activity;
+pub mod analysis;
+pub mod athlete;
+pub mod blob;
+pub mod club;
+pub mod effort;
+pub mod gear;
+pub mod heatmap;
+pub mod lap;
+pub mod photo;
+pub mod power_curve;
+pub mod route;
+pub mod segment;
+pub mod social;
+pub mod stored_object;
+pub mod training_load;
+pub mod zones;
@@ -0,0 +1,157 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use serde::Serialize;
+use crate::ApiError;
+use crate::strava::parsers::Photo;
+
+#[derive(Insertable)]
+#[diesel(table_name=crate::schema::activity_photos)]
+pub struct NewPhotoRow {
+    pub unique_id: String,
+    pub activity_id: i64,
+    pub caption: Option<String>,
+    pub lat: Option<f64>,
+    pub lng: Option<f64>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub source: Option<i32>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+}
+
+impl NewPhotoRow {
+    // `size` is the key of the version that gets downloaded
+    pub fn from_photo(activity_id: i64, photo: &Photo, size: Option<&str>) -> NewPhotoRow {
+        let (lat, lng) = match photo.location.as_deref() {
+            Some([lat, lng]) => (Some(*lat), Some(*lng)),
+            _ => (None, None),
+        };
+        let dimensions = size.and_then(|size| photo.dimensions(size));
+        NewPhotoRow {
+            unique_id: photo.unique_id.clone(),
+            activity_id,
+            caption: photo.caption.clone().filter(|caption| !caption.is_empty()),
+            lat,
+            lng,
+            created_at: photo.created_at,
+            source: photo.source,
+            width: dimensions.map(|[width, _]| width),
+            height: dimensions.map(|[_, height]| height),
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name=crate::schema::activity_photos)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PhotoRow {
+    pub unique_id: String,
+    pub activity_id: i64,
+    pub caption: Option<String>,
+    pub lat: Option<f64>,
+    pub lng: Option<f64>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub source: Option<i32>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    #[serde(skip_serializing)]
+    pub hash: Option<String>,
+    pub content_type: Option<String>,
+    pub downloaded_at: Option<NaiveDateTime>,
+}
+
+// Metadata only, photos already downloaded keep their blob
+pub async fn upsert_photos(conn: Object, rows: Vec<NewPhotoRow>) -> Result<usize, ApiError> {
+    use crate::schema::activity_photos::dsl::*;
+
+    conn.interact(move |conn| {
+        diesel::insert_into(activity_photos)
+            .values(&rows)
+            .on_conflict(unique_id)
+            .do_update()
+            .set((
+                activity_id.eq(excluded(activity_id)),
+                caption.eq(excluded(caption)),
+                lat.eq(excluded(lat)),
+                lng.eq(excluded(lng)),
+                created_at.eq(excluded(created_at)),
+                source.eq(excluded(source)),
+                width.eq(excluded(width)),
+                height.eq(excluded(height)),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
+
+pub async fn set_photo_blob(
+    conn: Object,
+    photo_id: String,
+    photo_hash: String,
+    photo_content_type: Option<String>,
+) -> Result<usize, ApiError> {
+    use crate::schema::activity_photos::dsl::*;
+
+    conn.interact(move |conn| {
+        diesel::update(activity_photos.find(photo_id))
+            .set((
+                hash.eq(Some(photo_hash)),
+                content_type.eq(photo_content_type),
+                downloaded_at.eq(Some(Utc::now().naive_utc())),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
+
+pub async fn downloaded_photo_count(conn: Object, activity: i64) -> Result<i64, ApiError> {
+    use crate::schema::activity_photos::dsl::*;
+
+    conn.interact(move |conn| {
+        activity_photos
+            .filter(activity_id.eq(activity))
+            .filter(hash.is_not_null())
+            .count()
+            .get_result(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_activity_photos(conn: Object, activity: i64) -> Result<Vec<PhotoRow>, ApiError> {
+    use crate::schema::activity_photos::dsl::*;
+
+    conn.interact(move |conn| {
+        activity_photos
+            .filter(activity_id.eq(activity))
+            .order((created_at.asc().nulls_last(), unique_id.asc()))
+            .select(PhotoRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_photo(conn: Object, activity: i64, photo_id: String) -> Result<Option<PhotoRow>, ApiError> {
+    use crate::schema::activity_photos::dsl::*;
+
+    conn.interact(move |conn| {
+        activity_photos
+            .find(photo_id)
+            .filter(activity_id.eq(activity))
+            .select(PhotoRow::as_select())
+            .first(conn)
+            .optional()
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
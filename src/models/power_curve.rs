@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use crate::ApiError;
+use crate::analysis::power::CurvePoint;
+
+#[derive(Insertable)]
+#[diesel(table_name=crate::schema::power_curve_points)]
+pub struct NewPowerCurveRow {
+    pub activity_id: i64,
+    pub duration: i32,
+    pub watts: f32,
+    pub athlete_id: i64,
+    pub start_date: DateTime<Utc>,
+}
+
+pub async fn get_activity_power_curve(conn: Object, activity: i64) -> Result<Vec<CurvePoint>, ApiError> {
+    use crate::schema::power_curve_points::dsl::*;
+
+    let points: Vec<(i32, f32)> = conn
+        .interact(move |conn| {
+            power_curve_points
+                .filter(activity_id.eq(activity))
+                .select((duration, watts))
+                .order(duration.asc())
+                .load(conn)
+        })
+        .await
+        .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+        .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })?;
+    Ok(points.into_iter().map(|(d, w)| CurvePoint { duration: d, watts: w }).collect())
+}
+
+// Best power of any activity in [after, before) for every duration
+pub async fn get_athlete_power_curve(
+    conn: Object,
+    athlete: i64,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) -> Result<Vec<CurvePoint>, ApiError> {
+    use crate::schema::power_curve_points::dsl::*;
+
+    let points: Vec<(i32, Option<f32>)> = conn
+        .interact(move |conn| {
+            let mut query = power_curve_points
+                .filter(athlete_id.eq(athlete))
+                .group_by(duration)
+                .select((duration, diesel::dsl::max(watts)))
+                .order(duration.asc())
+                .into_boxed();
+            if let Some(after) = after {
+                query = query.filter(start_date.ge(after));
+            }
+            if let Some(before) = before {
+                query = query.filter(start_date.lt(before));
+            }
+            query.load(conn)
+        })
+        .await
+        .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+        .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })?;
+    Ok(points
+        .into_iter()
+        .filter_map(|(d, w)| w.map(|w| CurvePoint { duration: d, watts: w }))
+        .collect())
+}
@@ -0,0 +1,179 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use serde::Serialize;
+use crate::ApiError;
+use crate::strava::parsers::Route;
+
+// What the route list gives, the full polyline and the files come with the route itself
+#[derive(Insertable)]
+#[diesel(table_name=crate::schema::routes)]
+pub struct NewRouteRow {
+    pub id: i64,
+    pub athlete_id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub distance: f32,
+    pub elevation_gain: Option<f32>,
+    pub route_type: Option<i32>,
+    pub sub_type: Option<i32>,
+    pub private: bool,
+    pub starred: bool,
+    pub estimated_moving_time: Option<i32>,
+    pub summary_polyline: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl NewRouteRow {
+    pub fn from_route(athlete_id: i64, route: &Route) -> NewRouteRow {
+        NewRouteRow {
+            id: route.id,
+            athlete_id,
+            name: route.name.clone(),
+            description: route.description.clone().filter(|description| !description.is_empty()),
+            distance: route.distance,
+            elevation_gain: route.elevation_gain,
+            route_type: route.route_type,
+            sub_type: route.sub_type,
+            private: route.private,
+            starred: route.starred,
+            estimated_moving_time: route.estimated_moving_time,
+            summary_polyline: route.map.as_ref().and_then(|map| map.summary_polyline.clone()),
+            created_at: route.created_at,
+            updated_at: route.updated_at,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name=crate::schema::routes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct RouteRow {
+    pub id: i64,
+    pub athlete_id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub distance: f32,
+    pub elevation_gain: Option<f32>,
+    pub route_type: Option<i32>,
+    pub sub_type: Option<i32>,
+    pub private: bool,
+    pub starred: bool,
+    pub estimated_moving_time: Option<i32>,
+    pub summary_polyline: Option<String>,
+    pub polyline: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip_serializing)]
+    pub gpx_hash: Option<String>,
+    #[serde(skip_serializing)]
+    pub tcx_hash: Option<String>,
+    pub files_updated_at: Option<DateTime<Utc>>,
+}
+
+// Listed fields only, the full polyline and the files stay until they're fetched again
+pub async fn upsert_routes(conn: Object, rows: Vec<NewRouteRow>) -> Result<usize, ApiError> {
+    use crate::schema::routes::dsl::*;
+
+    conn.interact(move |conn| {
+        diesel::insert_into(routes)
+            .values(&rows)
+            .on_conflict(id)
+            .do_update()
+            .set((
+                athlete_id.eq(excluded(athlete_id)),
+                name.eq(excluded(name)),
+                description.eq(excluded(description)),
+                distance.eq(excluded(distance)),
+                elevation_gain.eq(excluded(elevation_gain)),
+                route_type.eq(excluded(route_type)),
+                sub_type.eq(excluded(sub_type)),
+                private.eq(excluded(private)),
+                starred.eq(excluded(starred)),
+                estimated_moving_time.eq(excluded(estimated_moving_time)),
+                summary_polyline.eq(excluded(summary_polyline)),
+                created_at.eq(excluded(created_at)),
+                updated_at.eq(excluded(updated_at)),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
+
+// Routes without files or changed on strava since they were fetched
+pub async fn routes_needing_files(conn: Object, athlete: i64) -> Result<Vec<i64>, ApiError> {
+    use crate::schema::routes::dsl::*;
+
+    conn.interact(move |conn| {
+        routes
+            .filter(athlete_id.eq(athlete))
+            .filter(files_updated_at.is_distinct_from(updated_at.nullable()))
+            .order(updated_at.desc())
+            .select(id)
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+// `version` is the updated_at of the route the files were exported from
+pub async fn save_route_files(
+    conn: Object,
+    route: i64,
+    full_polyline: Option<String>,
+    gpx: String,
+    tcx: String,
+    version: DateTime<Utc>,
+) -> Result<usize, ApiError> {
+    use crate::schema::routes::dsl::*;
+
+    conn.interact(move |conn| {
+        diesel::update(routes.find(route))
+            .set((
+                polyline.eq(full_polyline),
+                gpx_hash.eq(Some(gpx)),
+                tcx_hash.eq(Some(tcx)),
+                files_updated_at.eq(Some(version)),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
+
+pub async fn get_routes(conn: Object, athlete: i64) -> Result<Vec<RouteRow>, ApiError> {
+    use crate::schema::routes::dsl::*;
+
+    conn.interact(move |conn| {
+        routes
+            .filter(athlete_id.eq(athlete))
+            .order((starred.desc(), created_at.desc()))
+            .select(RouteRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_route(conn: Object, route: i64) -> Result<Option<RouteRow>, ApiError> {
+    use crate::schema::routes::dsl::*;
+
+    conn.interact(move |conn| {
+        routes
+            .find(route)
+            .select(RouteRow::as_select())
+            .first(conn)
+            .optional()
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
@@ -0,0 +1,183 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use serde::Serialize;
+use std::collections::HashMap;
+use crate::ApiError;
+use crate::strava::parsers::{Comment, SocialAthlete};
+
+#[derive(Insertable, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name=crate::schema::activity_comments)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CommentRow {
+    pub id: i64,
+    pub activity_id: i64,
+    pub athlete_id: Option<i64>,
+    pub firstname: Option<String>,
+    pub lastname: Option<String>,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl CommentRow {
+    pub fn from_comment(activity_id: i64, comment: &Comment) -> CommentRow {
+        CommentRow {
+            id: comment.id,
+            activity_id,
+            athlete_id: comment.athlete.id,
+            firstname: comment.athlete.firstname.clone(),
+            lastname: comment.athlete.lastname.clone(),
+            text: comment.text.clone(),
+            created_at: comment.created_at,
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name=crate::schema::activity_kudos)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct KudosRow {
+    pub activity_id: i64,
+    pub firstname: String,
+    pub lastname: String,
+    pub first_seen_at: NaiveDateTime,
+}
+
+impl KudosRow {
+    pub fn from_athlete(activity_id: i64, athlete: &SocialAthlete) -> KudosRow {
+        KudosRow {
+            activity_id,
+            firstname: athlete.firstname.clone().unwrap_or_default(),
+            lastname: athlete.lastname.clone().unwrap_or_default(),
+            first_seen_at: Utc::now().naive_utc(),
+        }
+    }
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name=crate::schema::activities)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SocialSyncRow {
+    pub id: i64,
+    pub start_date: DateTime<Utc>,
+    pub social_synced_at: Option<NaiveDateTime>,
+}
+
+// The search text is every stored comment, also the ones gone from strava
+const COMMENTS_TEXT_SQL: &str = "
+    UPDATE activities SET comments_text = (
+        SELECT string_agg(text, E'\\n' ORDER BY created_at, id) FROM activity_comments WHERE activity_id = $1
+    )
+    WHERE id = $1";
+
+// Comments are updated in place and kudos added, neither is ever removed
+pub async fn save_social(
+    conn: Object,
+    activity: i64,
+    comments: Vec<CommentRow>,
+    kudos: Vec<KudosRow>,
+) -> Result<(), ApiError> {
+    use crate::schema::{activities, activity_comments, activity_kudos};
+
+    conn.interact(move |conn| {
+        conn.transaction(|conn| {
+            diesel::insert_into(activity_comments::table)
+                .values(&comments)
+                .on_conflict(activity_comments::id)
+                .do_update()
+                .set(activity_comments::text.eq(excluded(activity_comments::text)))
+                .execute(conn)?;
+            diesel::insert_into(activity_kudos::table)
+                .values(&kudos)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            diesel::sql_query(COMMENTS_TEXT_SQL)
+                .bind::<diesel::sql_types::BigInt, _>(activity)
+                .execute(conn)?;
+            diesel::update(activities::table.find(activity))
+                .set(activities::social_synced_at.eq(Some(Utc::now().naive_utc())))
+                .execute(conn)
+        })
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })?;
+    Ok(())
+}
+
+pub async fn social_sync_states(conn: Object, athlete: i64) -> Result<Vec<SocialSyncRow>, ApiError> {
+    use crate::schema::activities::dsl::*;
+
+    conn.interact(move |conn| {
+        activities
+            .filter(athlete_id.eq(athlete))
+            .select(SocialSyncRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_comments(conn: Object, activity_ids: Vec<i64>) -> Result<Vec<CommentRow>, ApiError> {
+    use crate::schema::activity_comments::dsl::*;
+
+    conn.interact(move |conn| {
+        activity_comments
+            .filter(activity_id.eq_any(activity_ids))
+            .order((activity_id.asc(), created_at.asc(), id.asc()))
+            .select(CommentRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_kudos(conn: Object, activity_ids: Vec<i64>) -> Result<Vec<KudosRow>, ApiError> {
+    use crate::schema::activity_kudos::dsl::*;
+
+    conn.interact(move |conn| {
+        activity_kudos
+            .filter(activity_id.eq_any(activity_ids))
+            .order((activity_id.asc(), first_seen_at.asc(), firstname.asc(), lastname.asc()))
+            .select(KudosRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+// Comments and kudos per activity, activities with neither are left out
+pub async fn social_counts(conn: Object) -> Result<HashMap<i64, (i64, i64)>, ApiError> {
+    use crate::schema::{activity_comments, activity_kudos};
+    use diesel::dsl::count_star;
+
+    let (comment_counts, kudos_counts) = conn
+        .interact(move |conn| {
+            let comment_counts: Vec<(i64, i64)> = activity_comments::table
+                .group_by(activity_comments::activity_id)
+                .select((activity_comments::activity_id, count_star()))
+                .load(conn)?;
+            let kudos_counts: Vec<(i64, i64)> = activity_kudos::table
+                .group_by(activity_kudos::activity_id)
+                .select((activity_kudos::activity_id, count_star()))
+                .load(conn)?;
+            Ok::<_, diesel::result::Error>((comment_counts, kudos_counts))
+        })
+        .await
+        .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+        .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })?;
+
+    let mut counts: HashMap<i64, (i64, i64)> = HashMap::new();
+    for (activity_id, count) in comment_counts {
+        counts.entry(activity_id).or_default().0 = count;
+    }
+    for (activity_id, count) in kudos_counts {
+        counts.entry(activity_id).or_default().1 = count;
+    }
+    Ok(counts)
+}
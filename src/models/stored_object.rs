@@ -0,0 +1,49 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use crate::ApiError;
+
+#[derive(Insertable)]
+#[diesel(table_name=crate::schema::stored_objects)]
+pub struct NewStoredObjectRow {
+    pub key: String,
+    pub kind: String,
+    pub activity_id: Option<i64>,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub created_at: NaiveDateTime,
+}
+
+// Keep track of a blob written to the object store, the blob itself never goes in the db
+pub async fn create_stored_object(
+    conn: Object,
+    object_key: String,
+    object_kind: &str,
+    object_activity_id: Option<i64>,
+    object_content_type: &str,
+    object_size: usize,
+) -> Result<(), ApiError> {
+    use crate::schema::stored_objects::dsl::*;
+
+    let new_object = NewStoredObjectRow {
+        key: object_key,
+        kind: object_kind.to_string(),
+        activity_id: object_activity_id,
+        content_type: object_content_type.to_string(),
+        size_bytes: object_size as i64,
+        created_at: Utc::now().naive_utc(),
+    };
+    conn.interact(move |conn| {
+        diesel::insert_into(stored_objects)
+            .values(&new_object)
+            .on_conflict(key)
+            .do_nothing()
+            .execute(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })?;
+
+    Ok(())
+}
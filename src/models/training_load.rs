@@ -0,0 +1,117 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use serde::Serialize;
+use crate::ApiError;
+
+#[derive(Insertable)]
+#[diesel(table_name=crate::schema::athlete_thresholds)]
+pub struct NewThresholdRow {
+    pub athlete_id: i64,
+    pub effective_from: NaiveDate,
+    pub max_hr: Option<i32>,
+    pub resting_hr: Option<i32>,
+    pub ftp: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name=crate::schema::athlete_thresholds)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ThresholdRow {
+    pub athlete_id: i64,
+    pub effective_from: NaiveDate,
+    pub max_hr: Option<i32>,
+    pub resting_hr: Option<i32>,
+    pub ftp: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=crate::schema::activity_loads)]
+pub struct NewActivityLoadRow {
+    pub activity_id: i64,
+    pub athlete_id: i64,
+    pub start_date: DateTime<Utc>,
+    pub trimp: Option<f32>,
+    pub tss: Option<f32>,
+    pub load: f32,
+}
+
+// Setting thresholds again for the same date replaces them
+pub async fn save_thresholds(conn: Object, row: NewThresholdRow) -> Result<usize, ApiError> {
+    use crate::schema::athlete_thresholds::dsl::*;
+
+    conn.interact(move |conn| {
+        diesel::insert_into(athlete_thresholds)
+            .values(&row)
+            .on_conflict((athlete_id, effective_from))
+            .do_update()
+            .set((
+                max_hr.eq(excluded(max_hr)),
+                resting_hr.eq(excluded(resting_hr)),
+                ftp.eq(excluded(ftp)),
+                created_at.eq(excluded(created_at)),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })
+}
+
+pub async fn get_thresholds(conn: Object, athlete: i64) -> Result<Vec<ThresholdRow>, ApiError> {
+    use crate::schema::athlete_thresholds::dsl::*;
+
+    conn.interact(move |conn| {
+        athlete_thresholds
+            .filter(athlete_id.eq(athlete))
+            .order(effective_from.asc())
+            .select(ThresholdRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+// The thresholds in effect on `date`
+pub async fn get_thresholds_at(conn: Object, athlete: i64, date: NaiveDate) -> Result<Option<ThresholdRow>, ApiError> {
+    use crate::schema::athlete_thresholds::dsl::*;
+
+    conn.interact(move |conn| {
+        athlete_thresholds
+            .filter(athlete_id.eq(athlete))
+            .filter(effective_from.le(date))
+            .order(effective_from.desc())
+            .select(ThresholdRow::as_select())
+            .first(conn)
+            .optional()
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+// Start and load of every activity of the athlete before `before`
+pub async fn get_athlete_loads(
+    conn: Object,
+    athlete: i64,
+    before: DateTime<Utc>,
+) -> Result<Vec<(DateTime<Utc>, f32)>, ApiError> {
+    use crate::schema::activity_loads::dsl::*;
+
+    conn.interact(move |conn| {
+        activity_loads
+            .filter(athlete_id.eq(athlete))
+            .filter(start_date.lt(before))
+            .order(start_date.asc())
+            .select((start_date, load))
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Object;
+use serde::Serialize;
+use crate::ApiError;
+use crate::strava::parsers::ZoneBoundary;
+
+#[derive(Insertable, Queryable, Selectable)]
+#[diesel(table_name=crate::schema::athlete_zones)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ZoneRow {
+    pub athlete_id: i64,
+    pub kind: String,
+    pub source: String,
+    pub zone: i32,
+    pub min: i32,
+    pub max: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=crate::schema::activity_zone_times)]
+pub struct NewZoneTimeRow {
+    pub activity_id: i64,
+    pub kind: String,
+    pub zone: i32,
+    pub seconds: i32,
+    pub athlete_id: i64,
+    pub start_date: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name=crate::schema::activity_zone_times)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ZoneTimeRow {
+    pub kind: String,
+    pub zone: i32,
+    pub seconds: i32,
+}
+
+// Swap the zones of one kind and source for new ones
+pub async fn replace_zones(
+    conn: Object,
+    athlete: i64,
+    zone_kind: &'static str,
+    zone_source: &'static str,
+    zones: Vec<ZoneBoundary>,
+) -> Result<(), ApiError> {
+    use crate::schema::athlete_zones::dsl::*;
+
+    let rows: Vec<ZoneRow> = zones
+        .iter()
+        .enumerate()
+        .map(|(i, boundary)| ZoneRow {
+            athlete_id: athlete,
+            kind: zone_kind.to_string(),
+            source: zone_source.to_string(),
+            zone: i as i32 + 1,
+            min: boundary.min,
+            max: boundary.max,
+        })
+        .collect();
+    conn.interact(move |conn| {
+        conn.transaction(|conn| {
+            diesel::delete(
+                athlete_zones
+                    .filter(athlete_id.eq(athlete))
+                    .filter(kind.eq(zone_kind))
+                    .filter(source.eq(zone_source)),
+            )
+            .execute(conn)?;
+            diesel::insert_into(athlete_zones).values(&rows).execute(conn)
+        })
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB execute error".to_string() })?;
+    Ok(())
+}
+
+pub async fn get_zones(conn: Object, athlete: i64) -> Result<Vec<ZoneRow>, ApiError> {
+    use crate::schema::athlete_zones::dsl::*;
+
+    conn.interact(move |conn| {
+        athlete_zones
+            .filter(athlete_id.eq(athlete))
+            .order((kind.asc(), source.asc(), zone.asc()))
+            .select(ZoneRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+pub async fn get_activity_zone_times(conn: Object, activity: i64) -> Result<Vec<ZoneTimeRow>, ApiError> {
+    use crate::schema::activity_zone_times::dsl::*;
+
+    conn.interact(move |conn| {
+        activity_zone_times
+            .filter(activity_id.eq(activity))
+            .order((kind.asc(), zone.asc()))
+            .select(ZoneTimeRow::as_select())
+            .load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
+
+// (start, zone, seconds) of every activity of the athlete in [after, before)
+pub async fn get_athlete_zone_times(
+    conn: Object,
+    athlete: i64,
+    zone_kind: String,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) -> Result<Vec<(DateTime<Utc>, i32, i32)>, ApiError> {
+    use crate::schema::activity_zone_times::dsl::*;
+
+    conn.interact(move |conn| {
+        let mut query = activity_zone_times
+            .filter(athlete_id.eq(athlete))
+            .filter(kind.eq(zone_kind))
+            .select((start_date, zone, seconds))
+            .into_boxed();
+        if let Some(after) = after {
+            query = query.filter(start_date.ge(after));
+        }
+        if let Some(before) = before {
+            query = query.filter(start_date.lt(before));
+        }
+        query.order(start_date.asc()).load(conn)
+    })
+    .await
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB interaction error".to_string() })?
+    .map_err(|_| ApiError { status_code: StatusCode::INTERNAL_SERVER_ERROR, message: "DB query error".to_string() })
+}
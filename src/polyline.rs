@@ -0,0 +1,103 @@
+// Google's encoded polyline format, which strava uses for activity maps. Points are
+// [lat, lng] like the latlng stream, with 5 decimals.
+
+const PRECISION: f64 = 1e5;
+
+#[derive(Debug, PartialEq)]
+pub struct DecodeError;
+
+pub fn decode(encoded: &str) -> Result<Vec<[f64; 2]>, DecodeError> {
+    let mut points = Vec::new();
+    let mut bytes = encoded.bytes();
+    let (mut lat, mut lng) = (0i64, 0i64);
+    loop {
+        let Some(lat_delta) = next_value(&mut bytes)? else {
+            return Ok(points);
+        };
+        let lng_delta = next_value(&mut bytes)?.ok_or(DecodeError)?;
+        lat += lat_delta;
+        lng += lng_delta;
+        points.push([lat as f64 / PRECISION, lng as f64 / PRECISION]);
+    }
+}
+
+// One delta, None at the end of the input
+fn next_value(bytes: &mut impl Iterator<Item = u8>) -> Result<Option<i64>, DecodeError> {
+    let mut value = 0i64;
+    let mut shift = 0;
+    loop {
+        let Some(byte) = bytes.next() else {
+            return match shift {
+                0 => Ok(None),
+                _ => Err(DecodeError),
+            };
+        };
+        if !(63..127).contains(&byte) || shift > 60 {
+            return Err(DecodeError);
+        }
+        let chunk = (byte - 63) as i64;
+        value |= (chunk & 0x1f) << shift;
+        shift += 5;
+        if chunk < 0x20 {
+            break;
+        }
+    }
+    Ok(Some(match value & 1 {
+        1 => !(value >> 1),
+        _ => value >> 1,
+    }))
+}
+
+pub fn encode(points: &[[f64; 2]]) -> String {
+    let mut encoded = String::new();
+    let (mut lat, mut lng) = (0i64, 0i64);
+    for [point_lat, point_lng] in points {
+        let (next_lat, next_lng) = ((point_lat * PRECISION).round() as i64, (point_lng * PRECISION).round() as i64);
+        push_value(&mut encoded, next_lat - lat);
+        push_value(&mut encoded, next_lng - lng);
+        (lat, lng) = (next_lat, next_lng);
+    }
+    encoded
+}
+
+fn push_value(encoded: &mut String, delta: i64) {
+    let mut value = match delta < 0 {
+        true => !(delta << 1),
+        false => delta << 1,
+    };
+    while value >= 0x20 {
+        encoded.push((((value & 0x1f) | 0x20) as u8 + 63) as char);
+        value >>= 5;
+    }
+    encoded.push((value as u8 + 63) as char);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example from Google's documentation
+    const EXAMPLE: &str = "_p~iF~ps|U_ulLnnqC_mqNvxq`@";
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(EXAMPLE).unwrap(), vec![[38.5, -120.2], [40.7, -120.95], [43.252, -126.453]]);
+        assert!(decode("").unwrap().is_empty());
+        // Cut in the middle of a value, and a latitude without its longitude
+        assert_eq!(decode("_p~iF~ps|"), Err(DecodeError));
+        assert_eq!(decode("_p~iF"), Err(DecodeError));
+        assert_eq!(decode("_p~iF ps|U"), Err(DecodeError));
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(&[[38.5, -120.2], [40.7, -120.95], [43.252, -126.453]]), EXAMPLE);
+        assert_eq!(encode(&[]), "");
+
+        let track = [[50.846712, 4.352491], [50.846701, 4.352533], [-33.8567844, 151.213108]];
+        let decoded = decode(&encode(&track)).unwrap();
+        for (point, original) in decoded.iter().zip(track) {
+            assert!((point[0] - original[0]).abs() < 1e-5 && (point[1] - original[1]).abs() < 1e-5);
+        }
+    }
+}
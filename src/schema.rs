@@ -1,5 +1,174 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    activities (id) {
+        id -> Int8,
+        athlete_id -> Int8,
+        name -> Text,
+        sport_type -> Nullable<Text>,
+        distance -> Float4,
+        moving_time -> Int4,
+        elapsed_time -> Int4,
+        total_elevation_gain -> Nullable<Float4>,
+        start_date -> Timestamptz,
+        gear_id -> Nullable<Text>,
+        commute -> Bool,
+        trainer -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        description -> Nullable<Text>,
+        private_note -> Nullable<Text>,
+        detail_synced_at -> Nullable<Timestamp>,
+        calories -> Nullable<Float4>,
+        analyzed_at -> Nullable<Timestamp>,
+        summary_polyline -> Nullable<Text>,
+        polyline -> Nullable<Text>,
+        track_polyline -> Nullable<Text>,
+        social_synced_at -> Nullable<Timestamp>,
+        comments_text -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    activity_bounds (activity_id) {
+        activity_id -> Int8,
+        athlete_id -> Int8,
+        sport_type -> Nullable<Text>,
+        start_date -> Timestamptz,
+        min_lat -> Float8,
+        min_lng -> Float8,
+        max_lat -> Float8,
+        max_lng -> Float8,
+    }
+}
+
+diesel::table! {
+    activity_comments (id) {
+        id -> Int8,
+        activity_id -> Int8,
+        athlete_id -> Nullable<Int8>,
+        firstname -> Nullable<Text>,
+        lastname -> Nullable<Text>,
+        text -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    activity_kudos (activity_id, firstname, lastname) {
+        activity_id -> Int8,
+        firstname -> Text,
+        lastname -> Text,
+        first_seen_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    activity_laps (activity_id, lap_index) {
+        activity_id -> Int8,
+        lap_index -> Int4,
+        id -> Int8,
+        name -> Nullable<Text>,
+        elapsed_time -> Int4,
+        moving_time -> Int4,
+        start_date -> Timestamptz,
+        distance -> Float4,
+        start_index -> Nullable<Int4>,
+        end_index -> Nullable<Int4>,
+        total_elevation_gain -> Nullable<Float4>,
+        average_speed -> Nullable<Float4>,
+        max_speed -> Nullable<Float4>,
+        average_heartrate -> Nullable<Float4>,
+        max_heartrate -> Nullable<Float4>,
+        average_watts -> Nullable<Float4>,
+        average_cadence -> Nullable<Float4>,
+    }
+}
+
+diesel::table! {
+    activity_loads (activity_id) {
+        activity_id -> Int8,
+        athlete_id -> Int8,
+        start_date -> Timestamptz,
+        trimp -> Nullable<Float4>,
+        tss -> Nullable<Float4>,
+        load -> Float4,
+    }
+}
+
+diesel::table! {
+    activity_photos (unique_id) {
+        unique_id -> Text,
+        activity_id -> Int8,
+        caption -> Nullable<Text>,
+        lat -> Nullable<Float8>,
+        lng -> Nullable<Float8>,
+        created_at -> Nullable<Timestamptz>,
+        source -> Nullable<Int4>,
+        width -> Nullable<Int4>,
+        height -> Nullable<Int4>,
+        hash -> Nullable<Text>,
+        content_type -> Nullable<Text>,
+        downloaded_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    activity_splits (activity_id, units, split) {
+        activity_id -> Int8,
+        units -> Text,
+        split -> Int4,
+        distance -> Float4,
+        elapsed_time -> Int4,
+        moving_time -> Int4,
+        elevation_difference -> Nullable<Float4>,
+        average_speed -> Nullable<Float4>,
+        average_grade_adjusted_speed -> Nullable<Float4>,
+        average_heartrate -> Nullable<Float4>,
+        pace_zone -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    activity_zone_times (activity_id, kind, zone) {
+        activity_id -> Int8,
+        kind -> Text,
+        zone -> Int4,
+        seconds -> Int4,
+        athlete_id -> Int8,
+        start_date -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    athlete_clubs (athlete_id, club_id) {
+        athlete_id -> Int8,
+        club_id -> Int8,
+    }
+}
+
+diesel::table! {
+    athlete_thresholds (athlete_id, effective_from) {
+        athlete_id -> Int8,
+        effective_from -> Date,
+        max_hr -> Nullable<Int4>,
+        resting_hr -> Nullable<Int4>,
+        ftp -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    athlete_zones (athlete_id, kind, source, zone) {
+        athlete_id -> Int8,
+        kind -> Text,
+        source -> Text,
+        zone -> Int4,
+        min -> Int4,
+        max -> Int4,
+    }
+}
+
 diesel::table! {
     athletes (id) {
         id -> Int8,
@@ -12,18 +181,273 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    best_efforts (activity_id, kind, name) {
+        activity_id -> Int8,
+        kind -> Text,
+        name -> Text,
+        athlete_id -> Int8,
+        sport_type -> Nullable<Text>,
+        value -> Float4,
+        start_offset -> Int4,
+        start_date -> Timestamptz,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    blob_refs (hash, owner) {
+        hash -> Text,
+        owner -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    blobs (hash) {
+        hash -> Text,
+        size_bytes -> Int8,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    club_activities (key) {
+        key -> Text,
+        club_id -> Int8,
+        firstname -> Text,
+        lastname -> Text,
+        name -> Text,
+        sport_type -> Nullable<Text>,
+        distance -> Float4,
+        moving_time -> Int4,
+        elapsed_time -> Int4,
+        total_elevation_gain -> Float4,
+        workout_type -> Nullable<Int4>,
+        first_seen_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    club_members (club_id, firstname, lastname) {
+        club_id -> Int8,
+        firstname -> Text,
+        lastname -> Text,
+        membership -> Nullable<Text>,
+        admin -> Bool,
+        owner -> Bool,
+    }
+}
+
+diesel::table! {
+    clubs (id) {
+        id -> Int8,
+        name -> Text,
+        sport_type -> Nullable<Text>,
+        city -> Nullable<Text>,
+        state -> Nullable<Text>,
+        country -> Nullable<Text>,
+        private -> Bool,
+        member_count -> Nullable<Int4>,
+        url -> Nullable<Text>,
+        description -> Nullable<Text>,
+        club_type -> Nullable<Text>,
+        updated_at -> Timestamp,
+        members_synced_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    gear (id) {
+        id -> Text,
+        athlete_id -> Int8,
+        kind -> Text,
+        name -> Nullable<Text>,
+        brand_name -> Nullable<Text>,
+        model_name -> Nullable<Text>,
+        description -> Nullable<Text>,
+        is_primary -> Bool,
+        retired -> Bool,
+        distance -> Float4,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    gear_components (id) {
+        id -> Int4,
+        gear_id -> Text,
+        kind -> Text,
+        name -> Nullable<Text>,
+        installed_at -> Date,
+        removed_at -> Nullable<Date>,
+        service_distance -> Nullable<Float4>,
+        service_time -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    power_curve_points (activity_id, duration) {
+        activity_id -> Int8,
+        duration -> Int4,
+        watts -> Float4,
+        athlete_id -> Int8,
+        start_date -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    routes (id) {
+        id -> Int8,
+        athlete_id -> Int8,
+        name -> Text,
+        description -> Nullable<Text>,
+        distance -> Float4,
+        elevation_gain -> Nullable<Float4>,
+        route_type -> Nullable<Int4>,
+        sub_type -> Nullable<Int4>,
+        private -> Bool,
+        starred -> Bool,
+        estimated_moving_time -> Nullable<Int4>,
+        summary_polyline -> Nullable<Text>,
+        polyline -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        gpx_hash -> Nullable<Text>,
+        tcx_hash -> Nullable<Text>,
+        files_updated_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    segment_efforts (id) {
+        id -> Int8,
+        activity_id -> Int8,
+        segment_id -> Int8,
+        athlete_id -> Int8,
+        name -> Text,
+        elapsed_time -> Int4,
+        moving_time -> Int4,
+        start_date -> Timestamptz,
+        distance -> Float4,
+        start_index -> Nullable<Int4>,
+        end_index -> Nullable<Int4>,
+        average_watts -> Nullable<Float4>,
+        average_heartrate -> Nullable<Float4>,
+        max_heartrate -> Nullable<Float4>,
+        kom_rank -> Nullable<Int4>,
+        pr_rank -> Nullable<Int4>,
+        hidden -> Bool,
+    }
+}
+
+diesel::table! {
+    segments (id) {
+        id -> Int8,
+        name -> Text,
+        activity_type -> Nullable<Text>,
+        distance -> Float4,
+        average_grade -> Nullable<Float4>,
+        maximum_grade -> Nullable<Float4>,
+        elevation_high -> Nullable<Float4>,
+        elevation_low -> Nullable<Float4>,
+        start_lat -> Nullable<Float8>,
+        start_lng -> Nullable<Float8>,
+        end_lat -> Nullable<Float8>,
+        end_lng -> Nullable<Float8>,
+        climb_category -> Nullable<Int4>,
+        city -> Nullable<Text>,
+        state -> Nullable<Text>,
+        country -> Nullable<Text>,
+        private -> Bool,
+        updated_at -> Timestamp,
+        total_elevation_gain -> Nullable<Float4>,
+        polyline -> Nullable<Text>,
+        effort_count -> Nullable<Int4>,
+        athlete_count -> Nullable<Int4>,
+        star_count -> Nullable<Int4>,
+        detail_synced_at -> Nullable<Timestamp>,
+        detail_attempted_at -> Nullable<Timestamp>,
+        detail_missing -> Bool,
+    }
+}
+
+diesel::table! {
+    starred_segments (athlete_id, segment_id) {
+        athlete_id -> Int8,
+        segment_id -> Int8,
+    }
+}
+
+diesel::table! {
+    stored_objects (key) {
+        key -> Text,
+        kind -> Text,
+        activity_id -> Nullable<Int8>,
+        content_type -> Text,
+        size_bytes -> Int8,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     token (id) {
         id -> Int8,
-        expires_at -> Int4,
-        expires_in -> Int4,
+        expires_at -> Int8,
+        expires_in -> Int8,
         token_type -> Text,
         refresh_token -> Text,
         access_token -> Text,
     }
 }
 
+diesel::joinable!(activity_bounds -> activities (activity_id));
+diesel::joinable!(activity_comments -> activities (activity_id));
+diesel::joinable!(activity_kudos -> activities (activity_id));
+diesel::joinable!(activity_laps -> activities (activity_id));
+diesel::joinable!(activity_loads -> activities (activity_id));
+diesel::joinable!(activity_photos -> activities (activity_id));
+diesel::joinable!(activity_splits -> activities (activity_id));
+diesel::joinable!(activity_zone_times -> activities (activity_id));
+diesel::joinable!(athlete_clubs -> clubs (club_id));
+diesel::joinable!(best_efforts -> activities (activity_id));
+diesel::joinable!(blob_refs -> blobs (hash));
+diesel::joinable!(club_activities -> clubs (club_id));
+diesel::joinable!(club_members -> clubs (club_id));
+diesel::joinable!(gear_components -> gear (gear_id));
+diesel::joinable!(power_curve_points -> activities (activity_id));
+diesel::joinable!(segment_efforts -> activities (activity_id));
+diesel::joinable!(segment_efforts -> segments (segment_id));
+diesel::joinable!(starred_segments -> segments (segment_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
+    activities,
+    activity_bounds,
+    activity_comments,
+    activity_kudos,
+    activity_laps,
+    activity_loads,
+    activity_photos,
+    activity_splits,
+    activity_zone_times,
+    athlete_clubs,
+    athlete_thresholds,
+    athlete_zones,
     athletes,
+    best_efforts,
+    blob_refs,
+    blobs,
+    club_activities,
+    club_members,
+    clubs,
+    gear,
+    gear_components,
+    power_curve_points,
+    routes,
+    segment_efforts,
+    segments,
+    starred_segments,
+    stored_objects,
     token,
 );
@@ -0,0 +1,295 @@
+use crate::settings::env_names;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+
+// A value that must never end up in logs, Debug prints it as ***
+#[derive(Clone, Default, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: String) -> Secret {
+        Secret(value)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(***)")
+    }
+}
+
+// Somewhere settings can be read from. `key` is the setting name, e.g.
+// "strava.client_secret". Ok(None) means the provider doesn't have it and the next
+// one in the chain is asked.
+pub trait SecretProvider: Send + Sync {
+    fn name(&self) -> &str;
+    fn get(&self, key: &str) -> Result<Option<String>, String>;
+}
+
+// Process env, STRAVA_BACKUP_<SECTION>_<KEY> or the path of a file holding the value in
+// STRAVA_BACKUP_<SECTION>_<KEY>_FILE
+pub struct EnvProvider {
+    vars: HashMap<String, String>,
+}
+
+impl EnvProvider {
+    pub fn init(vars: HashMap<String, String>) -> EnvProvider {
+        EnvProvider { vars }
+    }
+}
+
+impl SecretProvider for EnvProvider {
+    fn name(&self) -> &str {
+        "env"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let names = env_names(key);
+        let name = &names[0];
+        let file_name = format!("{}_FILE", name);
+
+        match (self.vars.get(name), self.vars.get(&file_name)) {
+            (Some(_), Some(_)) => Err(format!("both {} and {} are set, use only one", name, file_name)),
+            (Some(value), None) => Ok(Some(value.clone())),
+            (None, Some(path)) => read_secret_file(&PathBuf::from(path)).map(Some),
+            // Older variable names are only looked at when the new ones are not set
+            (None, None) => Ok(names[1..].iter().find_map(|alias| self.vars.get(alias).cloned())),
+        }
+    }
+}
+
+// One file per setting in a directory, named after the key with dots replaced by
+// underscores, e.g. /run/secrets/strava_client_secret
+pub struct DirectoryProvider {
+    dir: PathBuf,
+}
+
+impl DirectoryProvider {
+    pub fn init(dir: &str) -> DirectoryProvider {
+        DirectoryProvider {
+            dir: PathBuf::from(dir),
+        }
+    }
+}
+
+impl SecretProvider for DirectoryProvider {
+    fn name(&self) -> &str {
+        "dir"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let path = self.dir.join(key.replace('.', "_"));
+        if !path.exists() {
+            return Ok(None);
+        }
+        read_secret_file(&path).map(Some)
+    }
+}
+
+// Runs a password manager, e.g. "pass show strava-backup/{key}" or
+// "op read op://private/strava-backup/{key}". No shell is involved, the template is split
+// on whitespace. A non zero exit is taken as "not there".
+pub struct CommandProvider {
+    template: String,
+}
+
+impl CommandProvider {
+    pub fn init(template: &str) -> CommandProvider {
+        CommandProvider {
+            template: template.to_string(),
+        }
+    }
+}
+
+impl SecretProvider for CommandProvider {
+    fn name(&self) -> &str {
+        "command"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let args: Vec<String> = self
+            .template
+            .split_whitespace()
+            .map(|arg| arg.replace("{key}", key))
+            .collect();
+        let Some((program, args)) = args.split_first() else {
+            return Err("secrets.command is empty".to_string());
+        };
+
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| format!("could not run {}: {}", program, e))?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let value = String::from_utf8(output.stdout)
+            .map_err(|_| format!("{} returned a value that is not utf8", program))?;
+        let value = value.trim_end_matches(['\r', '\n']);
+        Ok((!value.is_empty()).then(|| value.to_string()))
+    }
+}
+
+// Fixed values, so tests can build settings without touching the process env
+#[cfg(test)]
+pub struct StaticProvider {
+    values: HashMap<String, String>,
+}
+
+#[cfg(test)]
+impl StaticProvider {
+    pub fn init(values: HashMap<String, String>) -> StaticProvider {
+        StaticProvider { values }
+    }
+}
+
+#[cfg(test)]
+impl SecretProvider for StaticProvider {
+    fn name(&self) -> &str {
+        "static"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        Ok(self.values.get(key).cloned())
+    }
+}
+
+fn read_secret_file(path: &PathBuf) -> Result<String, String> {
+    fs::read_to_string(path)
+        .map(|secret| secret.trim_end_matches(['\r', '\n']).to_string())
+        .map_err(|e| format!("could not read secret file {}: {}", path.display(), e))
+}
+
+// Providers asked in order, the first one with a value wins. Lookups are cached so a
+// password manager is only run once per key.
+pub struct SecretChain {
+    providers: Vec<Box<dyn SecretProvider>>,
+    cache: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl SecretChain {
+    pub fn new(providers: Vec<Box<dyn SecretProvider>>) -> SecretChain {
+        SecretChain {
+            providers,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        if let Some(value) = self.cache.lock().unwrap().get(key) {
+            return Ok(value.clone());
+        }
+
+        let mut value = None;
+        for provider in &self.providers {
+            if let Some(found) = provider
+                .get(key)
+                .map_err(|e| format!("{} ({} provider)", e, provider.name()))?
+            {
+                value = Some(found);
+                break;
+            }
+        }
+        self.cache.lock().unwrap().insert(key.to_string(), value.clone());
+        Ok(value)
+    }
+}
+
+impl fmt::Debug for SecretChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let providers: Vec<&str> = self.providers.iter().map(|p| p.name()).collect();
+        let cache = self.cache.lock().unwrap();
+        let mut cached: Vec<(&String, &str)> = cache
+            .iter()
+            .map(|(key, value)| (key, if value.is_some() { "***" } else { "<unset>" }))
+            .collect();
+        cached.sort();
+        f.debug_struct("SecretChain")
+            .field("providers", &providers)
+            .field("cached", &cached)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct CountingProvider {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl SecretProvider for CountingProvider {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn get(&self, _key: &str) -> Result<Option<String>, String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Some("counted".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_chain_order_and_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let chain = SecretChain::new(vec![
+            Box::new(StaticProvider::init(HashMap::from([(
+                "strava.client_secret".to_string(),
+                "static-secret".to_string(),
+            )]))),
+            Box::new(CountingProvider { calls: calls.clone() }),
+        ]);
+
+        assert_eq!(chain.get("strava.client_secret").unwrap().unwrap(), "static-secret");
+        assert_eq!(chain.get("s3.secret_key").unwrap().unwrap(), "counted");
+        assert_eq!(chain.get("s3.secret_key").unwrap().unwrap(), "counted");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let chain = SecretChain::new(vec![Box::new(StaticProvider::init(HashMap::from([(
+            "strava.client_secret".to_string(),
+            "super-secret".to_string(),
+        )])))]);
+        chain.get("strava.client_secret").unwrap();
+
+        let debug = format!("{:?}", chain);
+        assert!(!debug.contains("super-secret"));
+        assert!(debug.contains("strava.client_secret"));
+        assert_eq!(format!("{:?}", Secret::new("super-secret".to_string())), "Secret(***)");
+    }
+
+    #[test]
+    fn test_directory_provider() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("strava_client_secret"), "from-dir\n").unwrap();
+
+        let provider = DirectoryProvider::init(dir.path().to_str().unwrap());
+        assert_eq!(provider.get("strava.client_secret").unwrap().unwrap(), "from-dir");
+        assert!(provider.get("s3.secret_key").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_command_provider() {
+        let provider = CommandProvider::init("echo secret-for-{key}");
+        assert_eq!(
+            provider.get("strava.client_secret").unwrap().unwrap(),
+            "secret-for-strava.client_secret"
+        );
+
+        assert!(CommandProvider::init("false").get("strava.client_secret").unwrap().is_none());
+        assert!(CommandProvider::init("does-not-exist-binary").get("a.b").is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::models::segment::{SegmentEffortRow, SegmentRow};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Serialize)]
+pub struct SegmentSummary {
+    #[serde(flatten)]
+    pub segment: SegmentRow,
+    pub starred: bool,
+    pub efforts: usize,
+    pub best_time: Option<i32>,
+    pub best_effort_id: Option<i64>,
+    pub last_effort: Option<DateTime<Utc>>,
+}
+
+// `rank` is among all the athlete's efforts on the segment, `rank_at_time` among the
+// efforts up to this one, which is how the athlete's standing changed over time. Equal
+// times share a rank.
+#[derive(Serialize)]
+pub struct RankedEffort {
+    #[serde(flatten)]
+    pub effort: SegmentEffortRow,
+    pub rank: usize,
+    pub rank_at_time: usize,
+    pub pr: bool,
+}
+
+// Ridden and starred segments, the most ridden first
+pub fn summaries(segments: Vec<SegmentRow>, efforts: &[SegmentEffortRow], starred: &[i64]) -> Vec<SegmentSummary> {
+    let starred: HashSet<i64> = starred.iter().copied().collect();
+    let mut by_segment: HashMap<i64, Vec<&SegmentEffortRow>> = HashMap::new();
+    for effort in efforts {
+        by_segment.entry(effort.segment_id).or_default().push(effort);
+    }
+
+    let mut summaries: Vec<SegmentSummary> = segments
+        .into_iter()
+        .map(|segment| {
+            let efforts = by_segment.remove(&segment.id).unwrap_or_default();
+            let best = efforts.iter().min_by_key(|effort| (effort.elapsed_time, effort.start_date));
+            SegmentSummary {
+                starred: starred.contains(&segment.id),
+                efforts: efforts.len(),
+                best_time: best.map(|effort| effort.elapsed_time),
+                best_effort_id: best.map(|effort| effort.id),
+                last_effort: efforts.iter().map(|effort| effort.start_date).max(),
+                segment,
+            }
+        })
+        .collect();
+    summaries.sort_by(|a, b| b.efforts.cmp(&a.efforts).then_with(|| a.segment.name.cmp(&b.segment.name)));
+    summaries
+}
+
+// Efforts on one segment, oldest first
+pub fn history(efforts: Vec<SegmentEffortRow>) -> Vec<RankedEffort> {
+    let times: Vec<i32> = efforts.iter().map(|effort| effort.elapsed_time).collect();
+    efforts
+        .into_iter()
+        .enumerate()
+        .map(|(i, effort)| {
+            let faster = |earlier: &[i32]| earlier.iter().filter(|time| **time < effort.elapsed_time).count();
+            let rank_at_time = faster(&times[..i]) + 1;
+            RankedEffort {
+                rank: faster(&times) + 1,
+                rank_at_time,
+                pr: times[..i].iter().all(|time| *time > effort.elapsed_time),
+                effort,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strava::parsers::SummarySegment;
+
+    fn effort(id: i64, segment_id: i64, date: &str, elapsed_time: i32) -> SegmentEffortRow {
+        SegmentEffortRow {
+            id,
+            activity_id: id,
+            segment_id,
+            athlete_id: 1,
+            name: "Hawk Hill".to_string(),
+            elapsed_time,
+            moving_time: elapsed_time,
+            start_date: DateTime::parse_from_rfc3339(date).unwrap().to_utc(),
+            distance: 2684.8,
+            start_index: None,
+            end_index: None,
+            average_watts: None,
+            average_heartrate: None,
+            max_heartrate: None,
+            kom_rank: None,
+            pr_rank: None,
+            hidden: false,
+        }
+    }
+
+    fn segment(id: i64, name: &str) -> SegmentRow {
+        let summary: SummarySegment = serde_json::from_value(serde_json::json!({ "id": id, "name": name, "distance": 1000.0 })).unwrap();
+        SegmentRow::from_segment(&summary)
+    }
+
+    #[test]
+    fn test_summaries() {
+        let efforts = vec![
+            effort(1, 10, "2024-01-01T09:00:00Z", 600),
+            effort(2, 10, "2024-02-01T09:00:00Z", 540),
+            effort(3, 20, "2024-02-01T10:00:00Z", 300),
+        ];
+        let summaries = summaries(vec![segment(20, "Alpe"), segment(10, "Hawk Hill"), segment(30, "Starred")], &efforts, &[30]);
+        assert_eq!(summaries.iter().map(|summary| summary.segment.id).collect::<Vec<_>>(), vec![10, 20, 30]);
+        assert_eq!((summaries[0].efforts, summaries[0].best_time, summaries[0].best_effort_id), (2, Some(540), Some(2)));
+        assert_eq!(summaries[0].last_effort, Some(efforts[1].start_date));
+        assert!(!summaries[0].starred);
+        assert_eq!((summaries[2].efforts, summaries[2].best_time, summaries[2].starred), (0, None, true));
+    }
+
+    #[test]
+    fn test_history() {
+        let efforts = vec![
+            effort(1, 10, "2024-01-01T09:00:00Z", 600),
+            effort(2, 10, "2024-02-01T09:00:00Z", 620),
+            effort(3, 10, "2024-03-01T09:00:00Z", 550),
+            effort(4, 10, "2024-04-01T09:00:00Z", 550),
+            effort(5, 10, "2024-05-01T09:00:00Z", 580),
+        ];
+        let ranks: Vec<(usize, usize, bool)> =
+            history(efforts).iter().map(|ranked| (ranked.rank, ranked.rank_at_time, ranked.pr)).collect();
+        assert_eq!(ranks, vec![(4, 1, true), (5, 2, false), (1, 1, true), (1, 1, false), (3, 3, false)]);
+    }
+}
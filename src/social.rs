@@ -0,0 +1,59 @@
+use crate::models::social::SocialSyncRow;
+use chrono::{DateTime, TimeDelta, Utc};
+
+// Comments and kudos come in mostly in the first days, so an activity is checked again
+// after an eighth of its age: hourly on the first day, about weekly after two months and
+// at most twice a year for old ones
+pub fn refresh_interval(age: TimeDelta) -> TimeDelta {
+    (age / 8).clamp(TimeDelta::hours(1), TimeDelta::days(180))
+}
+
+// Up to `limit` activities to refresh, the never refreshed ones first and then the most
+// recent, which are the most likely to have changed
+pub fn due_activities(rows: &[SocialSyncRow], now: DateTime<Utc>, limit: usize) -> Vec<i64> {
+    let mut due: Vec<&SocialSyncRow> = rows
+        .iter()
+        .filter(|row| match row.social_synced_at {
+            None => true,
+            Some(synced_at) => now - synced_at.and_utc() >= refresh_interval(now - row.start_date),
+        })
+        .collect();
+    due.sort_by_key(|row| (row.social_synced_at.is_some(), std::cmp::Reverse(row.start_date)));
+    due.into_iter().take(limit).map(|row| row.id).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: i64, start_date: &str, social_synced_at: Option<&str>) -> SocialSyncRow {
+        let date = |date: &str| DateTime::parse_from_rfc3339(date).unwrap().to_utc();
+        SocialSyncRow { id, start_date: date(start_date), social_synced_at: social_synced_at.map(|at| date(at).naive_utc()) }
+    }
+
+    #[test]
+    fn test_refresh_interval() {
+        assert_eq!(refresh_interval(TimeDelta::minutes(30)), TimeDelta::hours(1));
+        assert_eq!(refresh_interval(TimeDelta::days(8)), TimeDelta::days(1));
+        assert_eq!(refresh_interval(TimeDelta::days(10 * 365)), TimeDelta::days(180));
+    }
+
+    #[test]
+    fn test_due_activities() {
+        let now = DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z").unwrap().to_utc();
+        let rows = vec![
+            // Two days old, refreshed seven hours ago, due after six
+            row(1, "2024-05-30T12:00:00Z", Some("2024-06-01T05:00:00Z")),
+            // Two days old, refreshed five hours ago
+            row(2, "2024-05-30T12:00:00Z", Some("2024-06-01T07:00:00Z")),
+            // Years old, refreshed a month ago
+            row(3, "2020-06-01T12:00:00Z", Some("2024-05-01T12:00:00Z")),
+            row(4, "2019-01-01T12:00:00Z", None),
+            row(5, "2024-05-31T12:00:00Z", None),
+            // Years old, refreshed a year ago
+            row(6, "2020-06-01T12:00:00Z", Some("2023-06-01T12:00:00Z")),
+        ];
+        assert_eq!(due_activities(&rows, now, 10), vec![5, 4, 1, 6]);
+        assert_eq!(due_activities(&rows, now, 3), vec![5, 4, 1]);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::models::activity::ActivityRow;
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    Week,
+    Month,
+    Year,
+    SportType,
+    Gear,
+}
+
+// Totals of one group, distance and elevation in meters and times in seconds like strava
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct StatsBucket {
+    pub key: String,
+    pub count: i64,
+    pub distance: f64,
+    pub moving_time: i64,
+    pub elevation_gain: f64,
+    pub calories: f64,
+}
+
+// ISO week like "2024-W05", the first days of january can belong to the previous year
+pub fn week_key(date: DateTime<Utc>) -> String {
+    let week = date.iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+// Activities without sport type or gear go to "none"
+fn group_key(activity: &ActivityRow, group_by: GroupBy) -> String {
+    let date = activity.start_date;
+    match group_by {
+        GroupBy::Week => week_key(date),
+        GroupBy::Month => format!("{}-{:02}", date.year(), date.month()),
+        GroupBy::Year => date.year().to_string(),
+        GroupBy::SportType => activity.sport_type.clone().unwrap_or_else(|| "none".to_string()),
+        GroupBy::Gear => activity.gear_id.clone().unwrap_or_else(|| "none".to_string()),
+    }
+}
+
+// Sum the activities per group, ordered by key. Sums are f64 so thousands of f32
+// distances don't drift.
+pub fn aggregate(activities: &[ActivityRow], group_by: GroupBy) -> Vec<StatsBucket> {
+    let mut buckets: BTreeMap<String, StatsBucket> = BTreeMap::new();
+    for activity in activities {
+        let key = group_key(activity, group_by);
+        let bucket = buckets.entry(key.clone()).or_insert_with(|| StatsBucket { key, ..Default::default() });
+        bucket.count += 1;
+        bucket.distance += activity.distance as f64;
+        bucket.moving_time += activity.moving_time as i64;
+        bucket.elevation_gain += activity.total_elevation_gain.unwrap_or_default() as f64;
+        bucket.calories += activity.calories.unwrap_or_default() as f64;
+    }
+    buckets.into_values().collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // A stored activity with just what the aggregations look at
+    pub(crate) fn activity_row(id: i64, start_date: &str, sport_type: &str, distance: f32) -> ActivityRow {
+        let start_date = start_date.parse::<DateTime<Utc>>().unwrap();
+        ActivityRow {
+            id,
+            athlete_id: 28853829,
+            name: format!("Activity {}", id),
+            sport_type: Some(sport_type.to_string()),
+            distance,
+            moving_time: 3600,
+            elapsed_time: 3700,
+            total_elevation_gain: Some(100.0),
+            start_date,
+            gear_id: None,
+            commute: false,
+            trainer: false,
+            created_at: start_date.naive_utc(),
+            updated_at: start_date.naive_utc(),
+            description: None,
+            private_note: None,
+            detail_synced_at: None,
+            calories: Some(500.0),
+            analyzed_at: None,
+            summary_polyline: None,
+            polyline: None,
+            track_polyline: None,
+        }
+    }
+
+    fn fixtures() -> Vec<ActivityRow> {
+        let mut gravel = activity_row(4, "2024-02-03T08:00:00Z", "GravelRide", 60000.0);
+        gravel.gear_id = Some("b453542543".to_string());
+        gravel.calories = None;
+        vec![
+            activity_row(1, "2023-12-31T09:00:00Z", "Run", 10000.0),
+            activity_row(2, "2024-01-01T09:00:00Z", "Run", 5000.0),
+            activity_row(3, "2024-01-20T07:30:00Z", "Ride", 40000.0),
+            gravel,
+        ]
+    }
+
+    #[test]
+    fn test_group_by_time() {
+        let years = aggregate(&fixtures(), GroupBy::Year);
+        assert_eq!(years.iter().map(|b| b.key.as_str()).collect::<Vec<_>>(), vec!["2023", "2024"]);
+        assert_eq!(years[1].count, 3);
+        assert_eq!(years[1].distance, 105000.0);
+        assert_eq!(years[1].moving_time, 3 * 3600);
+        assert_eq!(years[1].elevation_gain, 300.0);
+        assert_eq!(years[1].calories, 1000.0);
+
+        let months = aggregate(&fixtures(), GroupBy::Month);
+        assert_eq!(months.iter().map(|b| b.key.as_str()).collect::<Vec<_>>(), vec!["2023-12", "2024-01", "2024-02"]);
+
+        // Sunday 2023-12-31 and monday 2024-01-01 are in different ISO weeks
+        let weeks = aggregate(&fixtures(), GroupBy::Week);
+        assert_eq!(
+            weeks.iter().map(|b| b.key.as_str()).collect::<Vec<_>>(),
+            vec!["2023-W52", "2024-W01", "2024-W03", "2024-W05"]
+        );
+    }
+
+    #[test]
+    fn test_group_by_sport_and_gear() {
+        let sports = aggregate(&fixtures(), GroupBy::SportType);
+        let run = sports.iter().find(|b| b.key == "Run").unwrap();
+        assert_eq!((run.count, run.distance), (2, 15000.0));
+        assert_eq!(sports.len(), 3);
+
+        let gear = aggregate(&fixtures(), GroupBy::Gear);
+        assert_eq!(gear.iter().map(|b| (b.key.as_str(), b.count)).collect::<Vec<_>>(), vec![("b453542543", 1), ("none", 3)]);
+
+        // The totals don't depend on how they are grouped
+        let total: f64 = gear.iter().map(|b| b.distance).sum();
+        assert_eq!(total, aggregate(&fixtures(), GroupBy::Year).iter().map(|b| b.distance).sum::<f64>());
+        assert!(aggregate(&[], GroupBy::Week).is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::settings::StorageSettings;
+use crate::storage::content_hash;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Local content addressed store. Blobs live in root/ab/cd/abcd... where abcd... is the
+// sha256 of the content, so writing the same bytes twice is a no-op.
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct VerifyReport {
+    pub checked: usize,
+    pub corrupted: Vec<String>,
+}
+
+impl BlobStore {
+    pub fn init(root: &str) -> BlobStore {
+        BlobStore {
+            root: PathBuf::from(root),
+        }
+    }
+
+    pub fn from_settings(settings: &StorageSettings) -> BlobStore {
+        BlobStore::init(&settings.blob_dir)
+    }
+
+    pub fn path_for(&self, hash: &str) -> PathBuf {
+        self.root.join(&hash[..2]).join(&hash[2..4]).join(hash)
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.path_for(hash).exists()
+    }
+
+    // Returns the hash of the content. The file is written next to its final place and
+    // renamed so a crash never leaves a half written blob under a valid hash.
+    pub fn put(&self, bytes: &[u8]) -> std::io::Result<String> {
+        let hash = content_hash(bytes);
+        let path = self.path_for(&hash);
+        if path.exists() {
+            return Ok(hash);
+        }
+
+        let dir = path.parent().expect("Blob paths always have a parent");
+        fs::create_dir_all(dir)?;
+        let tmp_path = dir.join(format!("{}.tmp", hash));
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+
+        Ok(hash)
+    }
+
+    pub fn get(&self, hash: &str) -> std::io::Result<Vec<u8>> {
+        fs::read(self.path_for(hash))
+    }
+
+    pub fn delete(&self, hash: &str) -> std::io::Result<()> {
+        match fs::remove_file(self.path_for(hash)) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    // Re-hash every blob on disk and report the ones that don't match their name
+    pub fn verify(&self) -> std::io::Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+        if !self.root.exists() {
+            return Ok(report);
+        }
+
+        let mut paths = vec![];
+        collect_files(&self.root, &mut paths)?;
+        for path in paths {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            if name.ends_with(".tmp") {
+                continue;
+            }
+            report.checked += 1;
+            if content_hash(&fs::read(&path)?) != name {
+                report.corrupted.push(name);
+            }
+        }
+        report.corrupted.sort();
+
+        Ok(report)
+    }
+}
+
+fn collect_files(dir: &Path, paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, paths)?;
+        } else {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_put_is_deduplicated() {
+        let dir = tempdir().unwrap();
+        let store = BlobStore::init(dir.path().to_str().unwrap());
+
+        let hash = store.put(b"some stream").unwrap();
+        assert_eq!(store.put(b"some stream").unwrap(), hash);
+        assert_eq!(store.get(&hash).unwrap(), b"some stream");
+
+        let path = store.path_for(&hash);
+        assert!(path.starts_with(dir.path().join(&hash[..2]).join(&hash[2..4])));
+        assert_eq!(store.verify().unwrap().checked, 1);
+    }
+
+    #[test]
+    fn test_verify_finds_corruption() {
+        let dir = tempdir().unwrap();
+        let store = BlobStore::init(dir.path().to_str().unwrap());
+
+        let good = store.put(b"good").unwrap();
+        let bad = store.put(b"bad").unwrap();
+        fs::write(store.path_for(&bad), b"flipped bits").unwrap();
+
+        let report = store.verify().unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.corrupted, vec![bad]);
+        assert!(store.contains(&good));
+    }
+}
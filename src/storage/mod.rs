@@ -0,0 +1,120 @@
+
+pub mod blob_store;
+pub mod s3;
+
+use crate::ApiError;
+use crate::models::stored_object::create_stored_object;
+use crate::storage::s3::S3Store;
+use axum::http::StatusCode;
+use deadpool_diesel::postgres::Pool;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tokio_util::io::ReaderStream;
+
+// Hex encoded sha256 of the content, used as the address of every blob we store
+pub fn content_hash(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+// Key for a blob in the object store, e.g. "activities/ab/ab12...". The first two
+// characters of the hash are used as a prefix so listings stay manageable.
+pub fn content_key(kind: &str, bytes: &[u8]) -> String {
+    hash_key(kind, &content_hash(bytes))
+}
+
+fn hash_key(kind: &str, hash: &str) -> String {
+    format!("{}/{}/{}", kind, &hash[..2], hash)
+}
+
+// Upload the activity json as returned by strava and record where it went
+pub async fn archive_raw_activity(
+    store: &S3Store,
+    pool: &Pool,
+    activity_id: i64,
+    raw: &serde_json::Value,
+) -> Result<String, ApiError> {
+    let bytes = serde_json::to_vec(raw).map_err(|_| ApiError {
+        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        message: "Could not serialize activity".to_string(),
+    })?;
+    archive_object(store, pool, "activities", Some(activity_id), &bytes, "application/json").await
+}
+
+// Upload any blob under its content addressed key, the db only keeps the key
+pub async fn archive_object(
+    store: &S3Store,
+    pool: &Pool,
+    kind: &str,
+    activity_id: Option<i64>,
+    bytes: &[u8],
+    content_type: &str,
+) -> Result<String, ApiError> {
+    let key = store
+        .put_blob(kind, bytes, content_type)
+        .await
+        .map_err(|_| ApiError {
+            status_code: StatusCode::BAD_GATEWAY,
+            message: "Could not write to object store".to_string(),
+        })?;
+
+    let conn = pool.get().await.map_err(|_| ApiError {
+        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        message: "Connection not found".to_string(),
+    })?;
+    create_stored_object(conn, key.clone(), kind, activity_id, content_type, bytes.len()).await?;
+
+    Ok(key)
+}
+
+// archive_object for a file on disk, streamed to the store so a large export is never held
+// in memory. The file is read twice, once for the hash the key and signature need up front.
+pub async fn archive_file(
+    store: &S3Store,
+    pool: &Pool,
+    kind: &str,
+    path: &Path,
+    content_type: &str,
+) -> Result<String, ApiError> {
+    let read_error = |_| ApiError {
+        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        message: format!("Could not read {}", path.display()),
+    };
+    let write_error = |_| ApiError {
+        status_code: StatusCode::BAD_GATEWAY,
+        message: "Could not write to object store".to_string(),
+    };
+
+    let mut hasher = Sha256::new();
+    let size = std::io::copy(&mut std::fs::File::open(path).map_err(read_error)?, &mut hasher).map_err(read_error)?;
+    let hash = hex::encode(hasher.finalize());
+    let key = hash_key(kind, &hash);
+    if !store.object_exists(&key).await.map_err(write_error)? {
+        let file = tokio::fs::File::open(path).await.map_err(read_error)?;
+        let body = reqwest::Body::wrap_stream(ReaderStream::new(file));
+        store.put_object_stream(&key, body, size, &hash, content_type).await.map_err(write_error)?;
+    }
+
+    let conn = pool.get().await.map_err(|_| ApiError {
+        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        message: "Connection not found".to_string(),
+    })?;
+    create_stored_object(conn, key.clone(), kind, None, content_type, size as usize).await?;
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_key() {
+        let key = content_key("activities", b"hello");
+        assert_eq!(
+            key,
+            "activities/2c/2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(content_key("activities", b"hello"), key);
+        assert_ne!(content_key("streams", b"hello"), key);
+    }
+}
@@ -0,0 +1,298 @@
+use crate::settings::S3Settings;
+use crate::storage::content_key;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Client for any S3 compatible store (AWS, MinIO, R2...). Requests use path style
+// addressing (endpoint/bucket/key) which is what MinIO expects by default.
+pub struct S3Store {
+    endpoint: String,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Store {
+    pub fn init(
+        endpoint: &str,
+        bucket: &str,
+        region: &str,
+        access_key: &str,
+        secret_key: &str,
+    ) -> S3Store {
+        S3Store {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            access_key: access_key.to_string(),
+            secret_key: secret_key.to_string(),
+        }
+    }
+
+    pub fn from_settings(settings: &S3Settings) -> S3Store {
+        S3Store::init(
+            &settings.endpoint,
+            &settings.bucket,
+            &settings.region,
+            &settings.access_key,
+            settings.secret_key.expose(),
+        )
+    }
+
+    // Store the content under its content addressed key and return the key. If the
+    // object is already there the upload is skipped, the content can't have changed.
+    pub async fn put_blob(
+        &self,
+        kind: &str,
+        bytes: &[u8],
+        content_type: &str,
+    ) -> Result<String, reqwest::Error> {
+        let key = content_key(kind, bytes);
+        if !self.object_exists(&key).await? {
+            self.put_object(&key, bytes.to_vec(), content_type).await?;
+        }
+        Ok(key)
+    }
+
+    pub async fn put_object(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        content_type: &str,
+    ) -> Result<(), reqwest::Error> {
+        let client = reqwest::Client::new();
+        let request = client
+            .put(self.object_url(key))
+            .header("Content-Type", content_type);
+        let request = self.sign(request, "PUT", key, &bytes, Utc::now());
+        request.body(bytes).send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    // put_object for a body that is read as it uploads. S3 needs the length and the
+    // signature the hash of the content before the first byte is sent.
+    pub async fn put_object_stream(
+        &self,
+        key: &str,
+        body: reqwest::Body,
+        len: u64,
+        payload_hash: &str,
+        content_type: &str,
+    ) -> Result<(), reqwest::Error> {
+        let client = reqwest::Client::new();
+        let request = client
+            .put(self.object_url(key))
+            .header("Content-Type", content_type)
+            .header("Content-Length", len);
+        let request = self.sign_hashed(request, "PUT", key, payload_hash, Utc::now());
+        request.body(body).send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn object_exists(&self, key: &str) -> Result<bool, reqwest::Error> {
+        let client = reqwest::Client::new();
+        let request = self.sign(client.head(self.object_url(key)), "HEAD", key, b"", Utc::now());
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        response.error_for_status()?;
+        Ok(true)
+    }
+
+    fn object_path(&self, key: &str) -> String {
+        format!("/{}/{}", uri_encode(&self.bucket), uri_encode(key))
+    }
+
+    fn object_url(&self, key: &str) -> String {
+        format!("{}{}", self.endpoint, self.object_path(key))
+    }
+
+    // AWS signature version 4, see
+    // https://docs.aws.amazon.com/AmazonS3/latest/API/sig-v4-header-based-auth.html
+    fn sign(
+        &self,
+        request: reqwest::RequestBuilder,
+        method: &str,
+        key: &str,
+        payload: &[u8],
+        now: DateTime<Utc>,
+    ) -> reqwest::RequestBuilder {
+        let payload_hash = hex::encode(Sha256::digest(payload));
+        self.sign_hashed(request, method, key, &payload_hash, now)
+    }
+
+    fn sign_hashed(
+        &self,
+        request: reqwest::RequestBuilder,
+        method: &str,
+        key: &str,
+        payload_hash: &str,
+        now: DateTime<Utc>,
+    ) -> reqwest::RequestBuilder {
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let host = host_header(&self.endpoint);
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method,
+            self.object_path(key),
+            host,
+            payload_hash,
+            amz_date,
+            signed_headers,
+            payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = signing_key(&self.secret_key, &date, &self.region, "s3");
+        let signature = hex::encode(hmac(&signing_key, string_to_sign.as_bytes()));
+
+        request
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header(
+                "Authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                    self.access_key, scope, signed_headers, signature
+                ),
+            )
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let k_date = hmac(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    let k_region = hmac(&k_date, region.as_bytes());
+    let k_service = hmac(&k_region, service.as_bytes());
+    hmac(&k_service, b"aws4_request")
+}
+
+fn host_header(endpoint: &str) -> String {
+    let url = Url::parse(endpoint).expect("Invalid object store endpoint");
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }
+}
+
+// Percent encode everything but the unreserved characters, slashes are kept so keys
+// can have "directories"
+fn uri_encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_key() {
+        // Example from the AWS signature v4 documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex::encode(key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn test_uri_encode() {
+        assert_eq!(uri_encode("activities/ab/ab12"), "activities/ab/ab12");
+        assert_eq!(uri_encode("my file+1.gpx"), "my%20file%2B1.gpx");
+    }
+
+    #[test]
+    fn test_host_header() {
+        assert_eq!(host_header("http://localhost:9000"), "localhost:9000");
+        assert_eq!(host_header("https://s3.amazonaws.com"), "s3.amazonaws.com");
+    }
+
+    #[tokio::test]
+    async fn test_put_blob_skips_existing_objects() {
+        use wiremock::matchers::{method, path_regex};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path_regex("^/backups/activities/.*"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path_regex("^/backups/activities/.*"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let store = S3Store::init(&mock_server.uri(), "backups", "us-east-1", "minio", "minio123");
+        let key = store
+            .put_blob("activities", br#"{"id":1}"#, "application/json")
+            .await
+            .unwrap();
+        assert!(key.starts_with("activities/"));
+    }
+
+    #[tokio::test]
+    async fn test_put_object_stream_sends_the_whole_body() {
+        use wiremock::matchers::{body_bytes, header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let payload_hash = hex::encode(Sha256::digest(b"hello"));
+        let mock_server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/backups/exports/export.csv"))
+            .and(header("content-length", "5"))
+            .and(header("x-amz-content-sha256", payload_hash.as_str()))
+            .and(body_bytes(b"hello".to_vec()))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let store = S3Store::init(&mock_server.uri(), "backups", "us-east-1", "minio", "minio123");
+        let body = reqwest::Body::wrap_stream(tokio_util::io::ReaderStream::new(&b"hello"[..]));
+        store
+            .put_object_stream("exports/export.csv", body, 5, &payload_hash, "text/csv")
+            .await
+            .unwrap();
+    }
+}
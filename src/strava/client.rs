@@ -1,9 +1,15 @@
 use std::collections::HashSet;
-use crate::strava::parsers::{Athlete, Activity};
+use std::sync::Arc;
+use crate::crypto::TokenCipher;
+use crate::settings::StravaSettings;
+use crate::strava::parsers::{
+    Athlete, AthleteZones, Activity, Club, ClubActivity, ClubMember, Comment, DetailedSegment, Gear, Photo, Route,
+    SocialAthlete, SummarySegment,
+};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter};
+use std::io::BufReader;
 use std::io::prelude::*;
 use url::Url;
 
@@ -15,47 +21,90 @@ pub struct LoginUrl {
 // request to exchange the code from strava
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TokenSet {
-    expires_at: i64,
-    expires_in: i64,
-    token_type: String,
-    refresh_token: String,
-    access_token: String,
+    pub expires_at: i64,
+    pub expires_in: i64,
+    pub token_type: String,
+    pub refresh_token: String,
+    pub access_token: String,
 }
 
+// Why a request to strava failed, a missing or unreadable token file included.
+// RateLimited comes after backing off once didn't help, see rate_limit_wait.
+#[derive(Debug)]
+pub enum ClientError {
+    Token(&'static str),
+    RateLimited,
+    Request(reqwest::Error),
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(error: reqwest::Error) -> ClientError {
+        ClientError::Request(error)
+    }
+}
+
+impl ClientError {
+    // Strava doesn't know the object, asking again won't help
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, ClientError::Request(error) if error.status() == Some(reqwest::StatusCode::NOT_FOUND))
+    }
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Token(message) => write!(f, "{}", message),
+            ClientError::RateLimited => write!(f, "Strava's rate limit is used up"),
+            ClientError::Request(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+// Sent as the oauth state and checked when strava redirects back
+pub const LOGIN_STATE: &str = "123456";
+
 pub struct StravaClient {
     base_url: String,
+    authorize_url: String,
     client_id: i32,
     client_secret: String,
+    redirect_uri: String,
     token_file: String,
+    token_cipher: Arc<TokenCipher>,
 }
 
 impl StravaClient {
-    pub fn init(base_url: &str, client_secret: &str) -> StravaClient {
+    pub fn init(settings: &StravaSettings, token_cipher: Arc<TokenCipher>) -> StravaClient {
         StravaClient {
-            base_url: base_url.to_string(),
-            client_id: 118327,
-            client_secret: client_secret.to_string(),
-            token_file: "./tokens.txt".to_string(),
+            base_url: settings.base_url.trim_end_matches('/').to_string(),
+            authorize_url: settings.authorize_url.clone(),
+            client_id: settings.client_id,
+            client_secret: settings.client_secret.expose().to_string(),
+            redirect_uri: settings.redirect_uri.clone(),
+            token_file: settings.token_file.clone(),
+            token_cipher,
         }
     }
 
     pub async fn login_link(&self) -> LoginUrl {
-        let mut url_builder = Url::parse("https://www.strava.com").unwrap();
-        url_builder.set_path("/oauth/authorize");
+        let mut url_builder = Url::parse(&self.authorize_url).unwrap();
         url_builder
             .query_pairs_mut()
             .append_pair("client_id", &self.client_id.to_string())
-            .append_pair("redirect_uri", "http://localhost:3007/token_exchange")
+            .append_pair("redirect_uri", &self.redirect_uri)
             .append_pair("response_type", "code")
-            .append_pair("scope", "activity:read_all")
-            .append_pair("state", "123456");
+            // profile:read_all is for the heart rate and power zones
+            .append_pair("scope", "activity:read_all,profile:read_all")
+            .append_pair("state", LOGIN_STATE);
 
         LoginUrl {
             url: url_builder.to_string(),
         }
     }
 
-    pub async fn code_exchange(&self, code: &str) -> Result<TokenSet, reqwest::Error> {
+    pub async fn code_exchange(&self, code: &str) -> Result<TokenSet, ClientError> {
         let mut exchange_url = Url::parse(&self.base_url).unwrap();
         exchange_url.set_path("/api/v3/oauth/token");
         exchange_url
@@ -71,15 +120,13 @@ impl StravaClient {
 
         // Save this to a file before coming back to the function, read it just to be sure
         self.write_to_file(&token_set, &self.token_file)
-            .expect("Failed writing tokes to file");
+            .map_err(|_| ClientError::Token("Failed writing tokens to file"))?;
 
         Ok(token_set)
     }
 
-    pub async fn refresh_token(&self) -> Result<TokenSet, reqwest::Error> {
-        let content = self
-            .read_from_file(&self.token_file)
-            .expect("Could not read file");
+    pub async fn refresh_token(&self) -> Result<TokenSet, ClientError> {
+        let content = self.read_from_file(&self.token_file).map_err(ClientError::Token)?;
 
         let mut refresh_url = Url::parse(&self.base_url).unwrap();
         refresh_url.set_path("/api/v3/oauth/token");
@@ -96,58 +143,294 @@ impl StravaClient {
 
         // Save this to a file before coming back to the function, read it just to be sure
         self.write_to_file(&token_set, &self.token_file)
-            .expect("Failed writing tokes to file");
+            .map_err(|_| ClientError::Token("Failed writing tokens to file"))?;
 
         Ok(token_set)
     }
 
+    // The token file only ever holds the encrypted json, see crypto::TokenCipher
     fn write_to_file(&self, token_set: &TokenSet, token_file: &str) -> std::io::Result<()> {
+        let sealed = self
+            .token_cipher
+            .encrypt(&serde_json::to_string(token_set)?)
+            .map_err(std::io::Error::other)?;
         let mut file = File::create(token_file)?;
-        file.write_all(serde_json::to_string(token_set)?.as_bytes())?;
+        file.write_all(sealed.as_bytes())?;
         Ok(())
     }
 
-    fn read_from_file(&self, filename: &str) -> serde_json::error::Result<TokenSet> {
-        let content = fs::read_to_string(filename).expect("Could not open token file");
-        serde_json::from_str(&content)
+    fn read_from_file(&self, filename: &str) -> Result<TokenSet, &'static str> {
+        let content = fs::read_to_string(filename).map_err(|_| "Could not open token file")?;
+        // Files written before encryption was added are still plain json
+        let json = match content.trim_start().starts_with('{') {
+            true => content,
+            false => self.token_cipher.decrypt(content.trim())?,
+        };
+        serde_json::from_str(&json).map_err(|_| "Could not parse token file")
     }
 
-    pub async fn get_user(&self) -> Result<Athlete, reqwest::Error> {
-        let content = self
-            .read_from_file(&self.token_file)
-            .expect("Could not read file");
+    pub fn token_set(&self) -> Result<TokenSet, &'static str> {
+        if !std::path::Path::new(&self.token_file).exists() {
+            return Err("No tokens found, log in first");
+        }
+        self.read_from_file(&self.token_file)
+    }
 
-        let client = reqwest::Client::new();
-        let response = client
-            .get(format!("{}/api/v3/athlete", &self.base_url))
-            .header(
-                "Authorization",
-                "Bearer ".to_string() + &content.access_token,
-            )
-            .send()
-            .await?;
-        let athlete = response.error_for_status()?.json::<Athlete>().await?;
+    // Refresh the access token if it expires in the next minute, for long running syncs
+    pub async fn ensure_fresh_token(&self) -> Result<(), &'static str> {
+        let token_set = self.token_set()?;
+        if token_set.expires_at > chrono::Utc::now().timestamp() + 60 {
+            return Ok(());
+        }
+        self.refresh_token().await.map_err(|e| match e {
+            ClientError::Token(message) => message,
+            _ => "Could not refresh the access token",
+        })?;
+        Ok(())
+    }
+
+    // Rewrite the token file with the active key, used when rotating keys. Returns false
+    // when the file already uses it, the old key can be dropped from tokens.keys then.
+    pub fn reencrypt_token_file(&self) -> Result<bool, &'static str> {
+        let content = fs::read_to_string(&self.token_file).map_err(|_| "Could not open token file")?;
+        if self.token_cipher.is_current(content.trim()) {
+            return Ok(false);
+        }
+        let sealed = self.token_cipher.reencrypt(content.trim())?;
+        fs::write(&self.token_file, sealed).map_err(|_| "Failed writing tokens to file")?;
+        Ok(true)
+    }
+
+    // Every api call but the oauth ones goes through here, an error status is an error.
+    // A 429 waits out the 15 minute window once, the token may need a refresh after that.
+    async fn authorized_get(&self, url: &str, query: &[(&str, String)]) -> Result<reqwest::Response, ClientError> {
+        let mut backed_off = false;
+        loop {
+            let content = self.read_from_file(&self.token_file).map_err(ClientError::Token)?;
+
+            let client = reqwest::Client::new();
+            let response = client
+                .get(url)
+                .query(query)
+                .header(
+                    "Authorization",
+                    "Bearer ".to_string() + &content.access_token,
+                )
+                .send()
+                .await?;
+            if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response.error_for_status()?);
+            }
+            match rate_limit_wait(response.headers(), chrono::Utc::now().timestamp()) {
+                Some(wait) if !backed_off => tokio::time::sleep(wait).await,
+                _ => return Err(ClientError::RateLimited),
+            }
+            self.ensure_fresh_token().await.map_err(ClientError::Token)?;
+            backed_off = true;
+        }
+    }
+
+    pub async fn get_user(&self) -> Result<Athlete, ClientError> {
+        let response = self.authorized_get(&format!("{}/api/v3/athlete", &self.base_url), &[]).await?;
+        let athlete = response.json::<Athlete>().await?;
         Ok(athlete)
     }
 
-    pub async fn get_activities(&self) -> Result<Vec<Activity>, reqwest::Error> {
-        let content = self
-            .read_from_file(&self.token_file)
-            .expect("Could not read file");
+    // Needs the profile:read_all scope, older logins only have activity:read_all
+    pub async fn get_athlete_zones(&self) -> Result<AthleteZones, ClientError> {
+        let response = self.authorized_get(&format!("{}/api/v3/athlete/zones", &self.base_url), &[]).await?;
+        let zones = response.json::<AthleteZones>().await?;
+        Ok(zones)
+    }
 
-        let client = reqwest::Client::new();
-        let response = client
-            .get(format!("{}/api/v3/activities", &self.base_url))
-            .header(
-                "Authorization",
-                "Bearer ".to_string() + &content.access_token,
-            )
-            .send()
+    pub async fn get_gear(&self, id: &str) -> Result<Gear, ClientError> {
+        let response = self.authorized_get(&format!("{}/api/v3/gear/{}", &self.base_url, id), &[]).await?;
+        let gear = response.json::<Gear>().await?;
+        Ok(gear)
+    }
+
+    // The latest activities, keeping the full payload strava sent for archiving
+    pub async fn get_activities_raw(&self) -> Result<Vec<serde_json::Value>, ClientError> {
+        self.get_activities_page(1, 30, None).await
+    }
+
+    // One page of the activity list, oldest first when `after` (epoch seconds) is given
+    pub async fn get_activities_page(
+        &self,
+        page: u32,
+        per_page: u32,
+        after: Option<i64>,
+    ) -> Result<Vec<serde_json::Value>, ClientError> {
+        let mut query = vec![("page", page.to_string()), ("per_page", per_page.to_string())];
+        if let Some(after) = after {
+            query.push(("after", after.to_string()));
+        }
+
+        let response = self.authorized_get(&format!("{}/api/v3/activities", &self.base_url), &query).await?;
+        let activities = response.json::<Vec<serde_json::Value>>().await?;
+        Ok(activities)
+    }
+
+    pub async fn get_segment(&self, id: i64) -> Result<DetailedSegment, ClientError> {
+        let response = self.authorized_get(&format!("{}/api/v3/segments/{}", &self.base_url, id), &[]).await?;
+        let segment = response.json::<DetailedSegment>().await?;
+        Ok(segment)
+    }
+
+    pub async fn get_starred_segments_page(&self, page: u32, per_page: u32) -> Result<Vec<SummarySegment>, ClientError> {
+        let response = self
+            .authorized_get(&format!("{}/api/v3/segments/starred", &self.base_url), &page_query(page, per_page))
             .await?;
-        let activity = response.error_for_status()?.json::<Vec<Activity>>().await?;
+        let segments = response.json::<Vec<SummarySegment>>().await?;
+        Ok(segments)
+    }
+
+    // The detailed activity, it has the description, private note, laps and segment efforts
+    pub async fn get_activity_raw(&self, activity_id: i64) -> Result<serde_json::Value, ClientError> {
+        let response = self
+            .authorized_get(&format!("{}/api/v3/activities/{}", &self.base_url, activity_id), &[])
+            .await?;
+        let activity = response.json::<serde_json::Value>().await?;
         Ok(activity)
     }
 
+    // Raw body of the streams request, stored as is in the blob store
+    pub async fn get_activity_streams_raw(&self, activity_id: i64) -> Result<Vec<u8>, ClientError> {
+        let query = [
+            ("keys", "time,distance,latlng,altitude,velocity_smooth,heartrate,cadence,watts,temp,moving,grade_smooth".to_string()),
+            ("key_by_type", "false".to_string()),
+        ];
+        let response = self
+            .authorized_get(&format!("{}/api/v3/activities/{}/streams", &self.base_url, activity_id), &query)
+            .await?;
+        let streams = response.bytes().await?;
+        Ok(streams.to_vec())
+    }
+
+    // `size` is the longest side in pixels, strava caps it at the original
+    pub async fn get_activity_photos(&self, activity_id: i64, size: u32) -> Result<Vec<Photo>, ClientError> {
+        let query = [("size", size.to_string()), ("photo_sources", "true".to_string())];
+        let response = self
+            .authorized_get(&format!("{}/api/v3/activities/{}/photos", &self.base_url, activity_id), &query)
+            .await?;
+        let photos = response.json::<Vec<Photo>>().await?;
+        Ok(photos)
+    }
+
+    // Photo urls point at a CDN and don't take the token. Returns the bytes and the
+    // content type.
+    pub async fn download_photo(&self, url: &str) -> Result<(Vec<u8>, Option<String>), reqwest::Error> {
+        let response = reqwest::Client::new().get(url).send().await?.error_for_status()?;
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let bytes = response.bytes().await?;
+        Ok((bytes.to_vec(), content_type))
+    }
+
+    // Comments page with a cursor, the cursor of the last comment fetches the next page
+    pub async fn get_activity_comments_page(
+        &self,
+        activity_id: i64,
+        page_size: u32,
+        after_cursor: Option<&str>,
+    ) -> Result<Vec<Comment>, ClientError> {
+        let mut query = vec![("page_size", page_size.to_string())];
+        if let Some(cursor) = after_cursor {
+            query.push(("after_cursor", cursor.to_string()));
+        }
+
+        let response = self
+            .authorized_get(&format!("{}/api/v3/activities/{}/comments", &self.base_url, activity_id), &query)
+            .await?;
+        let comments = response.json::<Vec<Comment>>().await?;
+        Ok(comments)
+    }
+
+    pub async fn get_activity_kudos_page(
+        &self,
+        activity_id: i64,
+        page: u32,
+        per_page: u32,
+    ) -> Result<Vec<SocialAthlete>, ClientError> {
+        let response = self
+            .authorized_get(&format!("{}/api/v3/activities/{}/kudos", &self.base_url, activity_id), &page_query(page, per_page))
+            .await?;
+        let kudoers = response.json::<Vec<SocialAthlete>>().await?;
+        Ok(kudoers)
+    }
+
+    pub async fn get_routes_page(&self, athlete_id: i64, page: u32, per_page: u32) -> Result<Vec<Route>, ClientError> {
+        let response = self
+            .authorized_get(&format!("{}/api/v3/athletes/{}/routes", &self.base_url, athlete_id), &page_query(page, per_page))
+            .await?;
+        let routes = response.json::<Vec<Route>>().await?;
+        Ok(routes)
+    }
+
+    pub async fn get_route(&self, id: i64) -> Result<Route, ClientError> {
+        let response = self.authorized_get(&format!("{}/api/v3/routes/{}", &self.base_url, id), &[]).await?;
+        let route = response.json::<Route>().await?;
+        Ok(route)
+    }
+
+    pub async fn get_route_gpx(&self, id: i64) -> Result<Vec<u8>, ClientError> {
+        self.get_route_export(id, "export_gpx").await
+    }
+
+    pub async fn get_route_tcx(&self, id: i64) -> Result<Vec<u8>, ClientError> {
+        self.get_route_export(id, "export_tcx").await
+    }
+
+    // Raw file, stored as is in the blob store
+    async fn get_route_export(&self, id: i64, export: &str) -> Result<Vec<u8>, ClientError> {
+        let response = self.authorized_get(&format!("{}/api/v3/routes/{}/{}", &self.base_url, id, export), &[]).await?;
+        let file = response.bytes().await?;
+        Ok(file.to_vec())
+    }
+
+    pub async fn get_athlete_clubs_page(&self, page: u32, per_page: u32) -> Result<Vec<Club>, ClientError> {
+        let response = self
+            .authorized_get(&format!("{}/api/v3/athlete/clubs", &self.base_url), &page_query(page, per_page))
+            .await?;
+        let clubs = response.json::<Vec<Club>>().await?;
+        Ok(clubs)
+    }
+
+    pub async fn get_club(&self, id: i64) -> Result<Club, ClientError> {
+        let response = self.authorized_get(&format!("{}/api/v3/clubs/{}", &self.base_url, id), &[]).await?;
+        let club = response.json::<Club>().await?;
+        Ok(club)
+    }
+
+    pub async fn get_club_members_page(
+        &self,
+        club_id: i64,
+        page: u32,
+        per_page: u32,
+    ) -> Result<Vec<ClubMember>, ClientError> {
+        let response = self
+            .authorized_get(&format!("{}/api/v3/clubs/{}/members", &self.base_url, club_id), &page_query(page, per_page))
+            .await?;
+        let members = response.json::<Vec<ClubMember>>().await?;
+        Ok(members)
+    }
+
+    pub async fn get_club_activities_page(
+        &self,
+        club_id: i64,
+        page: u32,
+        per_page: u32,
+    ) -> Result<Vec<ClubActivity>, ClientError> {
+        let response = self
+            .authorized_get(&format!("{}/api/v3/clubs/{}/activities", &self.base_url, club_id), &page_query(page, per_page))
+            .await?;
+        let activities = response.json::<Vec<ClubActivity>>().await?;
+        Ok(activities)
+    }
+
     pub async fn write_activities(&self, activities: &Vec<Activity>, activities_file: &str) -> std::io::Result<()> {
         let mut act_set = HashSet::new();
 
@@ -170,11 +453,57 @@ impl StravaClient {
                 let _ = f.write_all("\n".as_bytes());
             }
         }
-        let _ = f.flush().unwrap();
+        f.flush().unwrap();
         Ok(())
     }
 }
 
+// Strava counts requests per 15 minutes, reset on the quarter hour, and per day, reset at
+// midnight UTC. The limits and usage come as "15min,daily" in the X-RateLimit headers, and
+// for reads also in the stricter X-ReadRateLimit ones. None when a daily limit is used up,
+// that's not worth waiting for.
+fn rate_limit_wait(headers: &reqwest::header::HeaderMap, now: i64) -> Option<std::time::Duration> {
+    let pair = |name: String| -> Option<(u32, u32)> {
+        let (window, daily) = headers.get(name)?.to_str().ok()?.split_once(',')?;
+        Some((window.trim().parse().ok()?, daily.trim().parse().ok()?))
+    };
+    for prefix in ["X-RateLimit", "X-ReadRateLimit"] {
+        let limit = pair(format!("{}-Limit", prefix));
+        let usage = pair(format!("{}-Usage", prefix));
+        if let (Some((_, daily_limit)), Some((_, daily_usage))) = (limit, usage)
+            && daily_usage >= daily_limit
+        {
+            return None;
+        }
+    }
+    // A few seconds past the reset, clocks differ
+    Some(std::time::Duration::from_secs((900 - now.rem_euclid(900)) as u64 + 5))
+}
+
+fn page_query(page: u32, per_page: u32) -> [(&'static str, String); 2] {
+    [("page", page.to_string()), ("per_page", per_page.to_string())]
+}
+
+#[test]
+fn test_rate_limit_wait() {
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    let headers = |limit: &'static str, usage: &'static str| {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-RateLimit-Limit", HeaderValue::from_static("200,2000"));
+        headers.insert("X-RateLimit-Usage", HeaderValue::from_static("120,900"));
+        headers.insert("X-ReadRateLimit-Limit", HeaderValue::from_static(limit));
+        headers.insert("X-ReadRateLimit-Usage", HeaderValue::from_static(usage));
+        headers
+    };
+    // Ten minutes into the window waits until just past its end
+    let now = 1_700_000_000 - 1_700_000_000 % 900 + 600;
+    assert_eq!(rate_limit_wait(&headers("100,1000", "101,900"), now), Some(std::time::Duration::from_secs(305)));
+    assert_eq!(rate_limit_wait(&headers("100,1000", "60,1000"), now), None);
+    // Without headers it's the 15 minute window too
+    assert_eq!(rate_limit_wait(&reqwest::header::HeaderMap::new(), now + 299), Some(std::time::Duration::from_secs(6)));
+}
+
 #[tokio::test]
 async fn test_get_user_request() {
     use wiremock::matchers::{method, path};
@@ -189,7 +518,47 @@ async fn test_get_user_request() {
         .mount(&mock_server)
         .await;
 
-    let sc = StravaClient::init(&mock_server.uri(), "mock-app-token");
+    let token_cipher = TokenCipher::init(
+        "test",
+        std::collections::HashMap::from([("test".to_string(), vec![0u8; 32])]),
+    )
+    .unwrap();
+    let settings = StravaSettings {
+        base_url: mock_server.uri(),
+        authorize_url: "https://www.strava.com/oauth/authorize".to_string(),
+        client_id: 118327,
+        client_secret: crate::secrets::Secret::new("mock-app-token".to_string()),
+        redirect_uri: "http://localhost:3007/token_exchange".to_string(),
+        token_file: "./tokens.txt".to_string(),
+    };
+    let sc = StravaClient::init(&settings, Arc::new(token_cipher));
     let at = sc.get_user().await.unwrap();
     assert_eq!(at.id, 28853829);
 }
+
+#[test]
+fn test_reencrypt_token_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let token_file = dir.path().join("tokens.txt");
+    fs::write(&token_file, r#"{"expires_at":1,"expires_in":1,"token_type":"Bearer","refresh_token":"r","access_token":"a"}"#).unwrap();
+
+    let token_cipher = TokenCipher::init(
+        "test",
+        std::collections::HashMap::from([("test".to_string(), vec![0u8; 32])]),
+    )
+    .unwrap();
+    let settings = StravaSettings {
+        base_url: "http://localhost".to_string(),
+        authorize_url: "https://www.strava.com/oauth/authorize".to_string(),
+        client_id: 118327,
+        client_secret: crate::secrets::Secret::new("mock-app-token".to_string()),
+        redirect_uri: "http://localhost:3007/token_exchange".to_string(),
+        token_file: token_file.to_str().unwrap().to_string(),
+    };
+    let sc = StravaClient::init(&settings, Arc::new(token_cipher));
+
+    // A plain file from before encryption is sealed once, then left alone
+    assert_eq!(sc.reencrypt_token_file(), Ok(true));
+    assert_eq!(sc.reencrypt_token_file(), Ok(false));
+    assert_eq!(sc.token_set().unwrap().access_token, "a");
+}
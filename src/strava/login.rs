@@ -0,0 +1,79 @@
+use crate::strava::client::LOGIN_STATE;
+use url::Url;
+
+// Where strava sends the user back to after the authorize page. On a headless box that
+// page can't load, but the url in the address bar still has everything we need, so the
+// user can paste that url (or just the code in it) back to us.
+pub fn code_from_redirect(input: &str) -> Result<String, &'static str> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("Nothing pasted");
+    }
+
+    let Ok(url) = Url::parse(input) else {
+        return validate_code(input);
+    };
+
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.to_string())
+    };
+    if param("error").is_some() {
+        return Err("Strava refused the login (access_denied)");
+    }
+    if param("state").as_deref() != Some(LOGIN_STATE) {
+        return Err("The state in the url doesn't match, start the login again");
+    }
+    if let Some(scope) = param("scope")
+        && !scope.split(',').any(|s| s == "activity:read_all")
+    {
+        return Err("Access to activities was not granted, tick it on the strava page");
+    }
+    match param("code") {
+        Some(code) => validate_code(&code),
+        None => Err("The url has no code in it"),
+    }
+}
+
+fn validate_code(code: &str) -> Result<String, &'static str> {
+    if code.len() < 16 || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("That doesn't look like a strava code, paste the whole url");
+    }
+    Ok(code.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "9c4c9c8e1c8a2c1a3b5d7f9e0a1b2c3d4e5f6a7b";
+
+    #[test]
+    fn test_full_redirect_url() {
+        let url = format!(
+            "http://localhost:3007/token_exchange?state=123456&code={}&scope=read,activity:read_all",
+            CODE
+        );
+        assert_eq!(code_from_redirect(&url).unwrap(), CODE);
+        assert_eq!(code_from_redirect(&format!("  {}\n", CODE)).unwrap(), CODE);
+    }
+
+    #[test]
+    fn test_invalid_redirects() {
+        let wrong_state = format!("http://localhost:3007/token_exchange?state=654321&code={}", CODE);
+        assert!(code_from_redirect(&wrong_state).is_err());
+
+        let denied = "http://localhost:3007/token_exchange?state=123456&error=access_denied";
+        assert!(code_from_redirect(denied).is_err());
+
+        let no_activities = format!(
+            "http://localhost:3007/token_exchange?state=123456&code={}&scope=read",
+            CODE
+        );
+        assert!(code_from_redirect(&no_activities).is_err());
+
+        assert!(code_from_redirect("not a code").is_err());
+        assert!(code_from_redirect("").is_err());
+    }
+}
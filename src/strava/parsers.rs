@@ -1,4 +1,5 @@
-use chrono::{DateTime, Datelike, Utc};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use serde;
 use serde::{Deserialize, Serialize};
 use url::Url;
@@ -14,8 +15,14 @@ pub username: Option<String>,
     pub profile: Url,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    // Only in the detailed athlete
+    #[serde(default)]
+    pub bikes: Vec<SummaryGear>,
+    #[serde(default)]
+    pub shoes: Vec<SummaryGear>,
 }
 
+#[cfg(test)]
 impl Athlete {
     fn new(athlete_data: &str) -> Result<Athlete, &'static str> {
         let at: Athlete = serde_json::from_str(athlete_data).unwrap();
@@ -24,19 +31,91 @@ impl Athlete {
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
-struct ActivityAthlete {
-    id: i64,
+pub struct SummaryGear {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub primary: bool,
+    #[serde(default)]
+    pub retired: bool,
+    pub distance: f32,
+}
+
+// From /gear/{id}, distance is what strava counts in meters
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Gear {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub brand_name: Option<String>,
+    #[serde(default)]
+    pub model_name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub primary: bool,
+    #[serde(default)]
+    pub retired: bool,
+    pub distance: f32,
+}
+
+impl Gear {
+    // Bike ids start with a b, shoe ids with a g
+    pub fn kind(&self) -> &'static str {
+        match self.id.starts_with('b') {
+            true => "bike",
+            false => "shoes",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ActivityAthlete {
+    pub id: i64,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Activity {
     pub id: i64,
-    athlete: ActivityAthlete,
-    name: String,
-    distance: f32,
-    moving_time: i32,
-    elapsed_time: i32,
-    start_date: DateTime<Utc>,
+    pub athlete: ActivityAthlete,
+    pub name: String,
+    pub distance: f32,
+    pub moving_time: i32,
+    pub elapsed_time: i32,
+    pub start_date: DateTime<Utc>,
+    #[serde(default)]
+    pub sport_type: Option<String>,
+    #[serde(default)]
+    pub total_elevation_gain: Option<f32>,
+    #[serde(default)]
+    pub gear_id: Option<String>,
+    #[serde(default)]
+    pub commute: bool,
+    #[serde(default)]
+    pub trainer: bool,
+    #[serde(default)]
+    pub manual: bool,
+    #[serde(default)]
+    pub map: Option<ActivityMap>,
+    #[serde(default)]
+    pub total_photo_count: i32,
+    // Only in the detailed activity
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub private_note: Option<String>,
+    #[serde(default)]
+    pub calories: Option<f32>,
+    #[serde(default)]
+    pub laps: Vec<Lap>,
+    #[serde(default)]
+    pub splits_metric: Vec<Split>,
+    #[serde(default)]
+    pub splits_standard: Vec<Split>,
+    #[serde(default)]
+    pub segment_efforts: Vec<SegmentEffort>,
 }
 
 impl Activity {
@@ -45,39 +124,379 @@ impl Activity {
     }
 }
 
-#[derive(Deserialize, Serialize)]
-struct ActivityStream {
-    #[serde(alias = "type")]
-    stream_type: String,
-    data: Vec<f32>,
-    series_type: String,
-    original_size: i32,
-    resolution: String,
+// Start and end index point into the activity's streams
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Lap {
+    pub id: i64,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub lap_index: i32,
+    pub elapsed_time: i32,
+    pub moving_time: i32,
+    pub start_date: DateTime<Utc>,
+    pub distance: f32,
+    #[serde(default)]
+    pub start_index: Option<i32>,
+    #[serde(default)]
+    pub end_index: Option<i32>,
+    #[serde(default)]
+    pub total_elevation_gain: Option<f32>,
+    #[serde(default)]
+    pub average_speed: Option<f32>,
+    #[serde(default)]
+    pub max_speed: Option<f32>,
+    #[serde(default)]
+    pub average_heartrate: Option<f32>,
+    #[serde(default)]
+    pub max_heartrate: Option<f32>,
+    #[serde(default)]
+    pub average_watts: Option<f32>,
+    #[serde(default)]
+    pub average_cadence: Option<f32>,
+}
+
+// Per kilometer in splits_metric, per mile in splits_standard
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Split {
+    pub split: i32,
+    pub distance: f32,
+    pub elapsed_time: i32,
+    pub moving_time: i32,
+    #[serde(default)]
+    pub elevation_difference: Option<f32>,
+    #[serde(default)]
+    pub average_speed: Option<f32>,
+    #[serde(default)]
+    pub average_grade_adjusted_speed: Option<f32>,
+    #[serde(default)]
+    pub average_heartrate: Option<f32>,
+    #[serde(default)]
+    pub pace_zone: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SegmentEffort {
+    pub id: i64,
+    pub name: String,
+    pub elapsed_time: i32,
+    pub moving_time: i32,
+    pub start_date: DateTime<Utc>,
+    pub distance: f32,
+    #[serde(default)]
+    pub start_index: Option<i32>,
+    #[serde(default)]
+    pub end_index: Option<i32>,
+    #[serde(default)]
+    pub average_watts: Option<f32>,
+    #[serde(default)]
+    pub average_heartrate: Option<f32>,
+    #[serde(default)]
+    pub max_heartrate: Option<f32>,
+    #[serde(default)]
+    pub kom_rank: Option<i32>,
+    #[serde(default)]
+    pub pr_rank: Option<i32>,
+    #[serde(default)]
+    pub hidden: bool,
+    pub segment: SummarySegment,
 }
 
-impl ActivityStream {
-    fn from(stream_data: &str) -> ActivityStream {
-        let stream = serde_json::from_str(stream_data).unwrap();
-        stream
+// Start and end are [lat, lng], empty or null on some segments
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SummarySegment {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub activity_type: Option<String>,
+    pub distance: f32,
+    #[serde(default)]
+    pub average_grade: Option<f32>,
+    #[serde(default)]
+    pub maximum_grade: Option<f32>,
+    #[serde(default)]
+    pub elevation_high: Option<f32>,
+    #[serde(default)]
+    pub elevation_low: Option<f32>,
+    #[serde(default)]
+    pub start_latlng: Option<Vec<f64>>,
+    #[serde(default)]
+    pub end_latlng: Option<Vec<f64>>,
+    #[serde(default)]
+    pub climb_category: Option<i32>,
+    #[serde(default)]
+    pub city: Option<String>,
+    #[serde(default)]
+    pub state: Option<String>,
+    #[serde(default)]
+    pub country: Option<String>,
+    #[serde(default)]
+    pub private: bool,
+}
+
+// From /activities/{id}/photos, urls and sizes are keyed by the size asked for. Photos
+// from instagram (source 2) can lack the metadata.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Photo {
+    pub unique_id: String,
+    #[serde(default)]
+    pub urls: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub sizes: Option<HashMap<String, [i32; 2]>>,
+    #[serde(default)]
+    pub caption: Option<String>,
+    #[serde(default)]
+    pub location: Option<Vec<f64>>,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub source: Option<i32>,
+}
+
+impl Photo {
+    // The size key and url of the biggest version
+    pub fn largest_url(&self) -> Option<(&str, &str)> {
+        self.urls
+            .iter()
+            .flatten()
+            .filter_map(|(size, url)| size.parse::<u32>().ok().map(|pixels| (pixels, size.as_str(), url.as_str())))
+            .max_by_key(|(pixels, _, _)| *pixels)
+            .map(|(_, size, url)| (size, url))
     }
+
+    pub fn dimensions(&self, size: &str) -> Option<[i32; 2]> {
+        self.sizes.as_ref()?.get(size).copied()
+    }
+}
+
+// Who commented or gave kudos. Strava only shares the first name and the initial of the
+// last name of other athletes, and no id.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SocialAthlete {
+    #[serde(default)]
+    pub id: Option<i64>,
+    #[serde(default)]
+    pub firstname: Option<String>,
+    #[serde(default)]
+    pub lastname: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Comment {
+    pub id: i64,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+    pub athlete: SocialAthlete,
+    // For the next page
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+// From /athletes/{id}/routes and /routes/{id}, only the single route has the full
+// polyline. `type` is 1 for rides and 2 for runs, `sub_type` the terrain.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Route {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub distance: f32,
+    #[serde(default)]
+    pub elevation_gain: Option<f32>,
+    #[serde(rename = "type", default)]
+    pub route_type: Option<i32>,
+    #[serde(default)]
+    pub sub_type: Option<i32>,
+    #[serde(default)]
+    pub private: bool,
+    #[serde(default)]
+    pub starred: bool,
+    #[serde(default)]
+    pub estimated_moving_time: Option<i32>,
+    #[serde(default)]
+    pub map: Option<ActivityMap>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// From /athlete/clubs and /clubs/{id}, the description and club type only come with the
+// single club
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Club {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub sport_type: Option<String>,
+    #[serde(default)]
+    pub city: Option<String>,
+    #[serde(default)]
+    pub state: Option<String>,
+    #[serde(default)]
+    pub country: Option<String>,
+    #[serde(default)]
+    pub private: bool,
+    #[serde(default)]
+    pub member_count: Option<i32>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub club_type: Option<String>,
+}
+
+// Members come like kudoers, by name only
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ClubMember {
+    #[serde(default)]
+    pub firstname: Option<String>,
+    #[serde(default)]
+    pub lastname: Option<String>,
+    // "member" or "pending"
+    #[serde(default)]
+    pub membership: Option<String>,
+    #[serde(default)]
+    pub admin: bool,
+    #[serde(default)]
+    pub owner: bool,
+}
+
+// From /clubs/{id}/activities, newest first. Strava leaves out the id and the date.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ClubActivity {
+    pub athlete: SocialAthlete,
+    pub name: String,
+    pub distance: f32,
+    pub moving_time: i32,
+    pub elapsed_time: i32,
+    #[serde(default)]
+    pub total_elevation_gain: f32,
+    #[serde(default)]
+    pub sport_type: Option<String>,
+    #[serde(default)]
+    pub workout_type: Option<i32>,
+}
+
+// From /segments/{id}, with the map and counts the summary leaves out
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DetailedSegment {
+    #[serde(flatten)]
+    pub segment: SummarySegment,
+    #[serde(default)]
+    pub total_elevation_gain: Option<f32>,
+    #[serde(default)]
+    pub map: Option<SegmentMap>,
+    #[serde(default)]
+    pub effort_count: Option<i32>,
+    #[serde(default)]
+    pub athlete_count: Option<i32>,
+    #[serde(default)]
+    pub star_count: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct SegmentMap {
+    #[serde(default)]
+    pub polyline: Option<String>,
+}
+
+// Encoded polylines of the route, null on manual and indoor activities
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ActivityMap {
+    #[serde(default)]
+    pub summary_polyline: Option<String>,
+    // Only in the detailed activity, with more points
+    #[serde(default)]
+    pub polyline: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct ZoneBoundary {
+    pub min: i32,
+    // -1 for the last zone, it has no upper bound
+    pub max: i32,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ZoneRanges {
+    #[serde(default)]
+    pub custom_zones: bool,
+    pub zones: Vec<ZoneBoundary>,
+}
+
+// Response of /athlete/zones, power is only there for athletes with a power meter
+#[derive(Deserialize, Serialize, Debug)]
+pub struct AthleteZones {
+    #[serde(default)]
+    pub heart_rate: Option<ZoneRanges>,
+    #[serde(default)]
+    pub power: Option<ZoneRanges>,
+}
+
+// Most streams are numbers, latlng holds pairs and moving holds flags
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(untagged)]
+pub enum StreamData {
+    Numbers(Vec<f32>),
+    LatLng(Vec<[f64; 2]>),
+    Flags(Vec<bool>),
+    Other(serde_json::Value),
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ActivityStream {
+    #[serde(alias = "type")]
+    pub stream_type: String,
+    pub data: StreamData,
+    pub series_type: String,
+    pub original_size: i32,
+    pub resolution: String,
 }
 
 #[derive(Deserialize, Serialize)]
 #[serde(transparent)]
-struct ActivityStreams {
-    streams: Vec<ActivityStream>,
+pub struct ActivityStreams {
+    pub streams: Vec<ActivityStream>,
 }
 
 impl ActivityStreams {
+    #[cfg(test)]
     fn from(streams_data: &str) -> ActivityStreams {
-        let acts = serde_json::from_str(streams_data).unwrap();
-        acts
+        serde_json::from_str(streams_data).unwrap()
+    }
+
+    // The body stored in the blob store, as returned by strava with key_by_type=false
+    pub fn parse(bytes: &[u8]) -> Result<ActivityStreams, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
+
+    // A numeric stream like "time", "distance", "watts" or "heartrate"
+    pub fn numbers(&self, stream_type: &str) -> Option<&[f32]> {
+        self.streams.iter().find(|s| s.stream_type == stream_type).and_then(|s| match &s.data {
+            StreamData::Numbers(data) => Some(data.as_slice()),
+            _ => None,
+        })
+    }
+
+    pub fn latlng(&self) -> Option<&[[f64; 2]]> {
+        self.streams.iter().find(|s| s.stream_type == "latlng").and_then(|s| match &s.data {
+            StreamData::LatLng(data) => Some(data.as_slice()),
+            _ => None,
+        })
+    }
+
+    // A true/false stream like "moving"
+    pub fn flags(&self, stream_type: &str) -> Option<&[bool]> {
+        self.streams.iter().find(|s| s.stream_type == stream_type).and_then(|s| match &s.data {
+            StreamData::Flags(data) => Some(data.as_slice()),
+            _ => None,
+        })
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use chrono::Datelike;
 
     #[test]
     fn test_get_athlete() {
@@ -100,6 +519,22 @@ mod test {
             athl.profile.as_str(),
             "https://graph.facebook.com/10156169906188476/picture?height=256&width=256"
         );
+        assert!(athl.bikes.is_empty());
+    }
+
+    #[test]
+    fn test_get_athlete_gear() {
+        let input = r#"{"id":28853829,"username":"gonza","resource_state":3,"firstname":"Gonzalo","lastname":"Garcia","created_at":"2018-03-09T23:01:47Z","updated_at":"2024-01-28T21:00:13Z","profile":"https://example.com/picture","bikes":[{"id":"b12345678987655","primary":true,"name":"EMC","resource_state":2,"distance":0}],"shoes":[{"id":"g12345678987655","primary":true,"name":"adidas","resource_state":2,"distance":4904}]}"#;
+        let athlete = Athlete::new(input).unwrap();
+        assert_eq!(athlete.bikes[0].id, "b12345678987655");
+        assert!(athlete.bikes[0].primary);
+        assert_eq!(athlete.shoes[0].distance, 4904.0);
+
+        let input = r#"{"id":"b1231","primary":false,"resource_state":3,"distance":388206,"brand_name":"BMC","model_name":"Teammachine","frame_type":3,"description":"My Bike.","name":"Roadie","retired":true}"#;
+        let gear: Gear = serde_json::from_str(input).unwrap();
+        assert_eq!(gear.kind(), "bike");
+        assert_eq!(gear.brand_name.as_deref(), Some("BMC"));
+        assert!(gear.retired);
     }
     #[test]
     fn test_parse_activity() {
@@ -111,7 +546,112 @@ mod test {
         assert_eq!(act.distance, 0.0);
         assert_eq!(act.moving_time, 18373);
         assert_eq!(act.elapsed_time, 18373);
-        assert_eq!(act.start_date.day(), 20)
+        assert_eq!(act.start_date.day(), 20);
+        assert_eq!(act.sport_type.unwrap(), "MountainBikeRide");
+        assert_eq!(act.gear_id.unwrap(), "b453542543");
+        assert!(!act.commute);
+        assert!(act.description.is_none());
+        assert!(act.private_note.is_none());
+        assert_eq!(act.calories, Some(0.0));
+        let map = act.map.unwrap();
+        assert!(map.polyline.is_none());
+        assert!(map.summary_polyline.is_none());
+        assert!(act.laps.is_empty() && act.segment_efforts.is_empty());
+    }
+
+    #[test]
+    fn test_parse_laps_and_segment_efforts() {
+        let act_data = r#"{"id": 1, "athlete": {"id": 2}, "name": "Lunch Ride", "distance": 28099, "moving_time": 4207, "elapsed_time": 4410, "start_date": "2018-02-16T14:52:54Z",
+            "segment_efforts": [{"id": 12345678987654321, "resource_state": 2, "name": "Tunnel Rd.", "activity": {"id": 1}, "athlete": {"id": 2}, "elapsed_time": 2038, "moving_time": 2038, "start_date": "2018-02-16T14:56:25Z", "start_date_local": "2018-02-16T06:56:25Z", "distance": 9434.8, "start_index": 211, "end_index": 2246, "average_cadence": 78.6, "device_watts": true, "average_watts": 237.6, "segment": {"id": 673683, "resource_state": 2, "name": "Tunnel Rd.", "activity_type": "Ride", "distance": 9220.7, "average_grade": 4.2, "maximum_grade": 25.8, "elevation_high": 426.5, "elevation_low": 43.4, "start_latlng": [37.8346153, -122.2520872], "end_latlng": [37.8476261, -122.2008944], "climb_category": 3, "city": "Oakland", "state": "CA", "country": "United States", "private": false, "hazardous": false, "starred": false}, "kom_rank": null, "pr_rank": null, "achievements": [], "hidden": false}],
+            "splits_metric": [{"distance": 1001.5, "elapsed_time": 141, "elevation_difference": 4.4, "moving_time": 141, "split": 1, "average_speed": 7.1, "pace_zone": 0}],
+            "laps": [{"id": 4479306946, "resource_state": 2, "name": "Lap 1", "elapsed_time": 1573, "moving_time": 1569, "start_date": "2018-02-16T14:52:54Z", "distance": 8046.72, "start_index": 0, "end_index": 1570, "total_elevation_gain": 276, "average_speed": 5.12, "max_speed": 9.5, "average_cadence": 78.6, "device_watts": true, "average_watts": 233.1, "lap_index": 1, "split": 1}]}"#;
+        let act = Activity::new(act_data).unwrap();
+        assert_eq!(act.laps[0].lap_index, 1);
+        assert_eq!(act.laps[0].end_index, Some(1570));
+        assert_eq!(act.splits_metric[0].pace_zone, Some(0));
+        assert!(act.splits_standard.is_empty());
+
+        let effort = &act.segment_efforts[0];
+        assert_eq!(effort.id, 12345678987654321);
+        assert!(effort.kom_rank.is_none());
+        assert_eq!(effort.segment.id, 673683);
+        assert_eq!(effort.segment.start_latlng, Some(vec![37.8346153, -122.2520872]));
+        assert_eq!(effort.segment.climb_category, Some(3));
+    }
+
+    #[test]
+    fn test_parse_photos() {
+        let photos_data = r#"[{"unique_id": "a1b2c3d4-0000-4000-8000-000000000001", "activity_id": 1, "resource_state": 2, "caption": "Summit", "source": 1, "uploaded_at": "2024-06-01T12:00:00Z", "created_at": "2024-06-01T11:00:00Z", "urls": {"100": "https://example.com/100.jpg", "5000": "https://example.com/5000.jpg", "600": "https://example.com/600.jpg"}, "sizes": {"5000": [4032, 3024]}, "default_photo": true, "location": [45.92, 6.87]},
+            {"unique_id": "instagram", "source": 2, "urls": null, "location": null}]"#;
+        let photos: Vec<Photo> = serde_json::from_str(photos_data).unwrap();
+        assert_eq!(photos[0].largest_url(), Some(("5000", "https://example.com/5000.jpg")));
+        assert_eq!(photos[0].dimensions("5000"), Some([4032, 3024]));
+        assert_eq!(photos[0].location, Some(vec![45.92, 6.87]));
+        assert!(photos[1].largest_url().is_none());
+    }
+
+    #[test]
+    fn test_parse_comments_and_kudos() {
+        let comments_data = r#"[{"id": 12345678987654321, "activity_id": 12345678987654321, "post_id": null, "resource_state": 2, "text": "Good job and keep the cat pictures coming!", "mentions_metadata": null, "created_at": "2018-02-08T19:25:39Z", "athlete": {"firstname": "Peter", "lastname": "S"}, "cursor": "abc123%20"}]"#;
+        let comments: Vec<Comment> = serde_json::from_str(comments_data).unwrap();
+        assert_eq!(comments[0].athlete.firstname.as_deref(), Some("Peter"));
+        assert!(comments[0].athlete.id.is_none());
+        assert_eq!(comments[0].cursor.as_deref(), Some("abc123%20"));
+
+        let kudos_data = r#"[{"firstname": "Peter", "lastname": "S"}, {"firstname": "Jane", "lastname": "D"}]"#;
+        let kudoers: Vec<SocialAthlete> = serde_json::from_str(kudos_data).unwrap();
+        assert_eq!(kudoers[1].lastname.as_deref(), Some("D"));
+    }
+
+    #[test]
+    fn test_parse_route() {
+        let route_data = r#"{"athlete": {"id": 12345, "resource_state": 1}, "description": "Loop over the hills", "distance": 48211.4, "elevation_gain": 712.5, "id": 3141592653589793238, "id_str": "3141592653589793238", "map": {"id": "r3141592653589793238", "summary_polyline": "}g|eFnpqjVl@En@Md@", "resource_state": 2}, "name": "Hill loop", "private": false, "starred": true, "timestamp": 1704099600, "type": 1, "sub_type": 1, "created_at": "2024-01-01T09:00:00Z", "updated_at": "2024-02-01T09:00:00Z", "estimated_moving_time": 7200, "segments": [], "waypoints": []}"#;
+        let route: Route = serde_json::from_str(route_data).unwrap();
+        assert_eq!(route.id, 3141592653589793238);
+        assert_eq!(route.route_type, Some(1));
+        assert!(route.starred);
+        let map = route.map.unwrap();
+        assert_eq!(map.summary_polyline.as_deref(), Some("}g|eFnpqjVl@En@Md@"));
+        assert!(map.polyline.is_none());
+    }
+
+    #[test]
+    fn test_parse_clubs() {
+        let club_data = r#"{"id": 1, "resource_state": 3, "name": "Team Strava Cycling", "profile_medium": "https://dgalywyr863hv.cloudfront.net/pictures/clubs/1/1582/4/medium.jpg", "cover_photo": null, "sport_type": "cycling", "city": "San Francisco", "state": "California", "country": "United States", "private": true, "member_count": 116, "featured": false, "verified": false, "url": "team-strava-bike", "membership": "member", "admin": false, "owner": false, "description": "Private club for Cyclists who work at Strava.", "club_type": "company", "post_count": 29, "owner_id": 759, "following_count": 107}"#;
+        let club: Club = serde_json::from_str(club_data).unwrap();
+        assert_eq!(club.club_type.as_deref(), Some("company"));
+        assert_eq!(club.member_count, Some(116));
+
+        let members_data = r#"[{"resource_state": 2, "firstname": "Peter", "lastname": "S.", "membership": "member", "admin": true, "owner": false}]"#;
+        let members: Vec<ClubMember> = serde_json::from_str(members_data).unwrap();
+        assert!(members[0].admin);
+
+        let activities_data = r#"[{"resource_state": 2, "athlete": {"resource_state": 2, "firstname": "Peter", "lastname": "S."}, "name": "World Championship", "distance": 2641.7, "moving_time": 577, "elapsed_time": 635, "total_elevation_gain": 8.8, "type": "Ride", "sport_type": "MountainBikeRide", "workout_type": null}]"#;
+        let activities: Vec<ClubActivity> = serde_json::from_str(activities_data).unwrap();
+        assert_eq!(activities[0].athlete.lastname.as_deref(), Some("S."));
+        assert_eq!(activities[0].sport_type.as_deref(), Some("MountainBikeRide"));
+        assert!(activities[0].workout_type.is_none());
+    }
+
+    #[test]
+    fn test_parse_detailed_segment() {
+        let segment_data = r#"{"id": 229781, "resource_state": 3, "name": "Hawk Hill", "activity_type": "Ride", "distance": 2684.82, "average_grade": 5.7, "maximum_grade": 14.2, "elevation_high": 245.3, "elevation_low": 92.4, "start_latlng": [37.8331119, -122.4834356], "end_latlng": [37.8280722, -122.4981393], "climb_category": 1, "city": "San Francisco", "state": "CA", "country": "United States", "private": false, "hazardous": false, "starred": false, "created_at": "2009-09-21T20:29:41Z", "updated_at": "2018-02-15T09:04:18Z", "total_elevation_gain": 155.733, "map": {"id": "s229781", "polyline": "}g|eFnpqjVl@En@Md@HbAd@d@^h@Xx@VbARjBDh@OPQf@w@d@k@XKXDFPH\\EbGT`AV`@v@|@NTNb@?XOb@cAxAWLuE@eAFMBoAv@eBt@q@b@}@tAeAt@i@dAC`AFZj@dB?~@[h@MbAVn@b@b@\\d@Eu@jAYvAMvAFVAn@P`@v@|@", "resource_state": 3}, "effort_count": 309974, "athlete_count": 30623, "star_count": 2428}"#;
+        let detail: DetailedSegment = serde_json::from_str(segment_data).unwrap();
+        assert_eq!(detail.segment.id, 229781);
+        assert_eq!(detail.segment.climb_category, Some(1));
+        assert_eq!(detail.total_elevation_gain, Some(155.733));
+        assert!(detail.map.unwrap().polyline.unwrap().starts_with("}g|eF"));
+        assert_eq!(detail.effort_count, Some(309974));
+    }
+
+    #[test]
+    fn test_athlete_zones() {
+        let zones_data = r#"{"heart_rate": {"custom_zones": false, "zones": [{"min": 0, "max": 123}, {"min": 123, "max": 153}, {"min": 153, "max": 169}, {"min": 169, "max": 184}, {"min": 184, "max": -1}]}}"#;
+        let zones: AthleteZones = serde_json::from_str(zones_data).unwrap();
+        let heart_rate = zones.heart_rate.unwrap();
+        assert_eq!(heart_rate.zones.len(), 5);
+        assert_eq!(heart_rate.zones[4], ZoneBoundary { min: 184, max: -1 });
+        assert!(zones.power.is_none());
     }
 
     #[test]
@@ -119,5 +659,14 @@ mod test {
         let stream_data = r#"[ {"type" : "distance", "data" : [ 2.9, 5.8, 8.5, 11.7, 15, 19, 23.2, 28, 32.8, 38.1, 43.8, 49.5 ], "series_type" : "distance", "original_size" : 12, "resolution" : "high"}]"#;
         let stream = ActivityStreams::from(stream_data);
         assert_eq!(stream.streams[0].stream_type, "distance");
+
+        let streams_data = r#"[{"type": "latlng", "data": [[38.603734, -122.864112], [38.603696, -122.864131]], "series_type": "distance", "original_size": 2, "resolution": "high"},
+            {"type": "time", "data": [0, 1], "series_type": "distance", "original_size": 2, "resolution": "high"},
+            {"type": "moving", "data": [false, true], "series_type": "distance", "original_size": 2, "resolution": "high"}]"#;
+        let streams = ActivityStreams::parse(streams_data.as_bytes()).unwrap();
+        assert_eq!(streams.numbers("time").unwrap(), &[0.0, 1.0]);
+        assert_eq!(streams.latlng().unwrap()[1], [38.603696, -122.864131]);
+        assert!(streams.numbers("moving").is_none());
+        assert!(streams.numbers("watts").is_none());
     }
 }
@@ -0,0 +1,560 @@
+use crate::ApiError;
+use crate::analysis::analyze_pending;
+use crate::models::activity::{NewActivityRow, has_activity_details, update_activity_details, upsert_activities};
+use crate::models::blob::{has_blob_ref, replace_blob_ref, take_unreferenced_blobs};
+use crate::models::club::{
+    ClubActivityRow, ClubMemberRow, ClubRow, clubs_due_for_members, insert_club_activities, replace_athlete_clubs,
+    replace_club_members, save_club_detail,
+};
+use crate::models::gear::{GearRow, activity_gear_ids, upsert_gear};
+use crate::models::lap::{LapRow, SplitRow, replace_laps};
+use crate::models::photo::{NewPhotoRow, downloaded_photo_count, get_activity_photos, set_photo_blob, upsert_photos};
+use crate::models::route::{NewRouteRow, routes_needing_files, save_route_files, upsert_routes};
+use crate::models::segment::{
+    SegmentEffortRow, SegmentRow, mark_segment_detail_failed, replace_segment_efforts, replace_starred_segments,
+    save_segment_detail, segments_without_details,
+};
+use crate::models::social::{CommentRow, KudosRow, save_social, social_sync_states};
+use crate::models::zones::replace_zones;
+use crate::social::due_activities;
+use crate::storage::{archive_object, archive_raw_activity};
+use crate::storage::blob_store::BlobStore;
+use crate::storage::s3::S3Store;
+use crate::strava::client::{ClientError, StravaClient};
+use crate::strava::parsers::{Activity, Comment, SocialAthlete};
+use axum::http::StatusCode;
+use chrono::Utc;
+use deadpool_diesel::postgres::Pool;
+use std::collections::HashMap;
+
+// Strava's maximum page size
+const PER_PAGE: u32 = 200;
+// Activities whose comments and kudos are refreshed per sync, two requests each
+const SOCIAL_BATCH: usize = 50;
+// Segments whose details are fetched per sync, the rest come on the next ones
+const SEGMENT_BATCH: i64 = 50;
+// Clubs whose members are refreshed per sync, a page per 200 members
+const CLUB_MEMBERS_BATCH: i64 = 5;
+// Longest side of the photos, strava sends the original when it's smaller
+const PHOTO_SIZE: u32 = 5000;
+// Blobs referenced more recently than this are left to a later gc
+const GC_GRACE: chrono::TimeDelta = chrono::TimeDelta::hours(1);
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub activities: usize,
+    pub details: usize,
+    pub streams: usize,
+    pub photos: usize,
+    pub routes: usize,
+    pub club_activities: usize,
+    pub social: usize,
+    pub analyzed: usize,
+}
+
+// The phases after the activities run long, more so when they wait out the rate limit,
+// so each starts with a token that won't expire halfway
+async fn fresh_token(sc: &StravaClient) -> Result<(), ApiError> {
+    sc.ensure_fresh_token().await.map_err(|e| ApiError {
+        status_code: StatusCode::UNAUTHORIZED,
+        message: e.to_string(),
+    })
+}
+
+// A request that failed is skipped and tried again on the next sync, unless strava's rate
+// limit is used up. Then every request after it fails too, so the sync stops.
+fn unless_rate_limited<T>(result: Result<T, ClientError>) -> Result<Option<T>, ApiError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ClientError::RateLimited) => Err(rate_limited()),
+        Err(_) => Ok(None),
+    }
+}
+
+fn rate_limited() -> ApiError {
+    ApiError {
+        status_code: StatusCode::TOO_MANY_REQUESTS,
+        message: ClientError::RateLimited.to_string(),
+    }
+}
+
+// A failed fetch the sync can't go on without
+fn fetch_error(error: ClientError, message: &str) -> ApiError {
+    match error {
+        ClientError::RateLimited => rate_limited(),
+        _ => ApiError {
+            status_code: StatusCode::BAD_GATEWAY,
+            message: message.to_string(),
+        },
+    }
+}
+
+pub(crate) async fn connection(pool: &Pool) -> Result<deadpool_diesel::postgres::Object, ApiError> {
+    pool.get().await.map_err(|_| ApiError {
+        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        message: "Connection not found".to_string(),
+    })
+}
+
+// Page through every activity that started after `after` (epoch seconds, the whole
+// history when None), storing the summaries, the raw payloads when an object store is
+// configured, and with `details` the detailed activities and streams. With `skip_synced`
+// details and streams already stored are not fetched again, which is what makes an
+// interrupted backfill resumable.
+pub async fn sync_activities(
+    sc: &StravaClient,
+    pool: &Pool,
+    blob_store: &BlobStore,
+    object_store: Option<&S3Store>,
+    after: Option<i64>,
+    details: bool,
+    skip_synced: bool,
+) -> Result<SyncReport, ApiError> {
+    let mut report = SyncReport::default();
+    let mut athlete_id = None;
+    let mut page = 1;
+    loop {
+        fresh_token(sc).await?;
+        let raw_activities = sc
+            .get_activities_page(page, PER_PAGE, Some(after.unwrap_or(0)))
+            .await
+            .map_err(|e| fetch_error(e, &format!("Could not fetch page {} of activities", page)))?;
+        if raw_activities.is_empty() {
+            break;
+        }
+
+        let activities = raw_activities
+            .iter()
+            .map(|raw| serde_json::from_value::<Activity>(raw.clone()))
+            .collect::<Result<Vec<Activity>, _>>()
+            .map_err(|_| ApiError {
+                status_code: StatusCode::BAD_GATEWAY,
+                message: "Could not parse activities".to_string(),
+            })?;
+        athlete_id = athlete_id.or(activities.first().map(|activity| activity.athlete.id));
+        let rows = activities.iter().map(NewActivityRow::from_activity).collect();
+        upsert_activities(connection(pool).await?, rows).await?;
+
+        for (raw, activity) in raw_activities.iter().zip(activities.iter()) {
+            if let Some(store) = object_store {
+                archive_raw_activity(store, pool, activity.id, raw).await?;
+            }
+            if !details {
+                continue;
+            }
+            if !(skip_synced && has_activity_details(connection(pool).await?, activity.id).await?) {
+                sync_activity_details(sc, pool, activity.id).await?;
+                report.details += 1;
+            }
+            // Backed up photos are skipped, so an interrupted sync picks up where it stopped
+            let photo_count = activity.total_photo_count as i64;
+            let photos_synced = photo_count == 0
+                || (skip_synced && downloaded_photo_count(connection(pool).await?, activity.id).await? >= photo_count);
+            if !photos_synced {
+                report.photos += sync_activity_photos(sc, blob_store, pool, activity.id).await?;
+            }
+            // Manual activities have no streams
+            if activity.manual {
+                continue;
+            }
+            let owner = format!("activity:{}:streams", activity.id);
+            if skip_synced && has_blob_ref(connection(pool).await?, owner).await? {
+                continue;
+            }
+            sync_activity_streams(sc, blob_store, object_store, pool, activity.id).await?;
+            report.streams += 1;
+        }
+
+        report.activities += activities.len();
+        page += 1;
+    }
+
+    // Zones first so the analysis below uses them
+    if let (true, Some(athlete_id)) = (details, athlete_id) {
+        sync_athlete_zones(sc, pool, athlete_id).await?;
+        sync_gear(sc, pool, athlete_id).await?;
+        sync_segments(sc, pool, athlete_id).await?;
+        report.routes = sync_routes(sc, blob_store, pool, athlete_id).await?;
+        report.club_activities = sync_clubs(sc, pool, athlete_id).await?;
+        report.social = sync_social(sc, pool, athlete_id).await?;
+    }
+    // Only what isn't analyzed yet, so this is cheap when nothing new came in
+    if details {
+        report.analyzed = analyze_pending(pool, blob_store).await?;
+    }
+
+    Ok(report)
+}
+
+// Keep a copy of the athlete's zones from strava. Logins from before the zones were
+// needed lack the scope for them, that's not worth failing the sync over.
+pub async fn sync_athlete_zones(sc: &StravaClient, pool: &Pool, athlete_id: i64) -> Result<(), ApiError> {
+    fresh_token(sc).await?;
+    let Some(zones) = unless_rate_limited(sc.get_athlete_zones().await)? else {
+        return Ok(());
+    };
+    for (kind, ranges) in [("heartrate", zones.heart_rate), ("power", zones.power)] {
+        if let Some(ranges) = ranges {
+            replace_zones(connection(pool).await?, athlete_id, kind, "strava", ranges.zones).await?;
+        }
+    }
+    Ok(())
+}
+
+// Bikes and shoes from the athlete and every gear an activity was done with, retired
+// gear only shows up on the activities. Like the zones, the athlete's gear needs the
+// profile:read_all scope and a gear strava won't return is skipped.
+pub async fn sync_gear(sc: &StravaClient, pool: &Pool, athlete_id: i64) -> Result<(), ApiError> {
+    fresh_token(sc).await?;
+    let mut ids = activity_gear_ids(connection(pool).await?, athlete_id).await?;
+    if let Some(athlete) = unless_rate_limited(sc.get_user().await)? {
+        ids.extend(athlete.bikes.into_iter().chain(athlete.shoes).map(|gear| gear.id));
+    }
+    ids.sort();
+    ids.dedup();
+    for id in ids {
+        if let Some(gear) = unless_rate_limited(sc.get_gear(&id).await)? {
+            upsert_gear(connection(pool).await?, GearRow::from_gear(athlete_id, gear)).await?;
+        }
+    }
+    Ok(())
+}
+
+// The athlete's starred segments, then the details of the segments that don't have them
+// yet, ridden or starred, SEGMENT_BATCH per sync. A failed lookup goes to the back of the
+// queue, one strava answers 404 for (private, deleted) isn't asked for again.
+pub async fn sync_segments(sc: &StravaClient, pool: &Pool, athlete_id: i64) -> Result<(), ApiError> {
+    fresh_token(sc).await?;
+    let mut starred = Vec::new();
+    let mut page = 1;
+    let complete = loop {
+        let Some(segments) = unless_rate_limited(sc.get_starred_segments_page(page, PER_PAGE).await)? else {
+            break false;
+        };
+        let last_page = segments.len() < PER_PAGE as usize;
+        starred.extend(segments.iter().map(SegmentRow::from_segment));
+        if last_page {
+            break true;
+        }
+        page += 1;
+    };
+    // Half a list would unstar the rest
+    if complete {
+        replace_starred_segments(connection(pool).await?, athlete_id, starred).await?;
+    }
+
+    for segment_id in segments_without_details(connection(pool).await?, SEGMENT_BATCH).await? {
+        match sc.get_segment(segment_id).await {
+            Ok(detail) => {
+                save_segment_detail(connection(pool).await?, SegmentRow::from_detail(&detail)).await?;
+            }
+            Err(ClientError::RateLimited) => return Err(rate_limited()),
+            Err(error) => {
+                mark_segment_detail_failed(connection(pool).await?, segment_id, error.is_not_found()).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Store the metadata of every photo and download the ones not in the blob store yet,
+// returning how many were downloaded. A photo that fails to download is left without a
+// hash and tried again on the next sync.
+pub async fn sync_activity_photos(
+    sc: &StravaClient,
+    blob_store: &BlobStore,
+    pool: &Pool,
+    activity_id: i64,
+) -> Result<usize, ApiError> {
+    let photos = sc
+        .get_activity_photos(activity_id, PHOTO_SIZE)
+        .await
+        .map_err(|e| fetch_error(e, "Could not fetch activity photos"))?;
+    let rows = photos
+        .iter()
+        .map(|photo| NewPhotoRow::from_photo(activity_id, photo, photo.largest_url().map(|(size, _)| size)))
+        .collect();
+    upsert_photos(connection(pool).await?, rows).await?;
+
+    let stored: HashMap<String, Option<String>> = get_activity_photos(connection(pool).await?, activity_id)
+        .await?
+        .into_iter()
+        .map(|row| (row.unique_id, row.hash))
+        .collect();
+    let mut downloaded = 0;
+    for photo in &photos {
+        let backed_up = stored.get(&photo.unique_id).cloned().flatten();
+        if backed_up.is_some_and(|hash| blob_store.contains(&hash)) {
+            continue;
+        }
+        let Some((_, url)) = photo.largest_url() else {
+            continue;
+        };
+        let Ok((bytes, content_type)) = sc.download_photo(url).await else {
+            continue;
+        };
+
+        let hash = blob_store.put(&bytes).map_err(|_| ApiError {
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "Could not write blob".to_string(),
+        })?;
+        // One blob per photo, a photo strava sends different bytes for lets go of the old one
+        let owner = format!("activity:{}:photo:{}", activity_id, photo.unique_id);
+        replace_blob_ref(connection(pool).await?, owner, hash.clone(), bytes.len()).await?;
+        set_photo_blob(connection(pool).await?, photo.unique_id.clone(), hash, content_type).await?;
+        downloaded += 1;
+    }
+    Ok(downloaded)
+}
+
+// Routes are upserted from the list, deleted ones are kept. The gpx and tcx exports are
+// fetched for new and changed routes. Returns how many routes got new files.
+pub async fn sync_routes(sc: &StravaClient, blob_store: &BlobStore, pool: &Pool, athlete_id: i64) -> Result<usize, ApiError> {
+    fresh_token(sc).await?;
+    let mut page = 1;
+    loop {
+        let Some(routes) = unless_rate_limited(sc.get_routes_page(athlete_id, page, PER_PAGE).await)? else {
+            break;
+        };
+        let last_page = routes.len() < PER_PAGE as usize;
+        let rows = routes.iter().map(|route| NewRouteRow::from_route(athlete_id, route)).collect();
+        upsert_routes(connection(pool).await?, rows).await?;
+        if last_page {
+            break;
+        }
+        page += 1;
+    }
+
+    let mut synced = 0;
+    for route_id in routes_needing_files(connection(pool).await?, athlete_id).await? {
+        let Some(route) = unless_rate_limited(sc.get_route(route_id).await)? else {
+            continue;
+        };
+        let Some(gpx) = unless_rate_limited(sc.get_route_gpx(route_id).await)? else {
+            continue;
+        };
+        let Some(tcx) = unless_rate_limited(sc.get_route_tcx(route_id).await)? else {
+            continue;
+        };
+        let gpx = store_route_file(blob_store, pool, format!("route:{}:gpx", route_id), &gpx).await?;
+        let tcx = store_route_file(blob_store, pool, format!("route:{}:tcx", route_id), &tcx).await?;
+        let polyline = route.map.and_then(|map| map.polyline);
+        save_route_files(connection(pool).await?, route_id, polyline, gpx, tcx, route.updated_at).await?;
+        synced += 1;
+    }
+    Ok(synced)
+}
+
+// One blob per file, an edited route lets go of the old export
+async fn store_route_file(blob_store: &BlobStore, pool: &Pool, owner: String, bytes: &[u8]) -> Result<String, ApiError> {
+    let hash = blob_store.put(bytes).map_err(|_| ApiError {
+        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        message: "Could not write blob".to_string(),
+    })?;
+    replace_blob_ref(connection(pool).await?, owner, hash.clone(), bytes.len()).await?;
+    Ok(hash)
+}
+
+// The athlete's clubs with their new activities, and the members of CLUB_MEMBERS_BATCH
+// of them. Returns how many club activities were new.
+pub async fn sync_clubs(sc: &StravaClient, pool: &Pool, athlete_id: i64) -> Result<usize, ApiError> {
+    fresh_token(sc).await?;
+    let mut clubs = Vec::new();
+    let mut page = 1;
+    let complete = loop {
+        let Some(listed) = unless_rate_limited(sc.get_athlete_clubs_page(page, PER_PAGE).await)? else {
+            break false;
+        };
+        let last_page = listed.len() < PER_PAGE as usize;
+        clubs.extend(listed.iter().map(ClubRow::from_club));
+        if last_page {
+            break true;
+        }
+        page += 1;
+    };
+    // Half a list would drop the athlete from the other clubs
+    if complete {
+        replace_athlete_clubs(connection(pool).await?, athlete_id, clubs.clone()).await?;
+    }
+
+    let ids = clubs.iter().map(|club| club.id).collect();
+    let members_due = clubs_due_for_members(connection(pool).await?, ids, CLUB_MEMBERS_BATCH).await?;
+    let mut new_activities = 0;
+    for club in clubs {
+        if let Some(detail) = unless_rate_limited(sc.get_club(club.id).await)? {
+            save_club_detail(connection(pool).await?, ClubRow::from_club(&detail)).await?;
+        }
+        // Half a list would drop the other members
+        if members_due.contains(&club.id)
+            && let Some(members) = unless_rate_limited(fetch_club_members(sc, club.id).await)?
+        {
+            replace_club_members(connection(pool).await?, club.id, members).await?;
+        }
+        new_activities += sync_club_activities(sc, pool, club.id).await?;
+    }
+    Ok(new_activities)
+}
+
+async fn fetch_club_members(sc: &StravaClient, club_id: i64) -> Result<Vec<ClubMemberRow>, ClientError> {
+    let mut members = Vec::new();
+    let mut page = 1;
+    loop {
+        let listed = sc.get_club_members_page(club_id, page, PER_PAGE).await?;
+        let last_page = listed.len() < PER_PAGE as usize;
+        members.extend(listed.iter().map(|member| ClubMemberRow::from_member(club_id, member)));
+        if last_page {
+            return Ok(members);
+        }
+        page += 1;
+    }
+}
+
+// Newest first, so paging stops at the first page with nothing new
+async fn sync_club_activities(sc: &StravaClient, pool: &Pool, club_id: i64) -> Result<usize, ApiError> {
+    let mut new_activities = 0;
+    let mut page = 1;
+    loop {
+        let listed = sc.get_club_activities_page(club_id, page, PER_PAGE).await;
+        let Some(activities) = unless_rate_limited(listed)? else {
+            return Ok(new_activities);
+        };
+        let last_page = activities.len() < PER_PAGE as usize;
+        let rows = activities.iter().map(|activity| ClubActivityRow::from_activity(club_id, activity)).collect();
+        let inserted = insert_club_activities(connection(pool).await?, rows).await?;
+        new_activities += inserted;
+        if last_page || inserted == 0 {
+            return Ok(new_activities);
+        }
+        page += 1;
+    }
+}
+
+// Refresh the comments and kudos of the activities that are due, see social::due_activities.
+// An activity that fails stays due and comes back on the next sync.
+pub async fn sync_social(sc: &StravaClient, pool: &Pool, athlete_id: i64) -> Result<usize, ApiError> {
+    fresh_token(sc).await?;
+    let states = social_sync_states(connection(pool).await?, athlete_id).await?;
+    let mut refreshed = 0;
+    for activity_id in due_activities(&states, Utc::now(), SOCIAL_BATCH) {
+        let Some(comments) = unless_rate_limited(fetch_comments(sc, activity_id).await)? else {
+            continue;
+        };
+        let Some(kudoers) = unless_rate_limited(fetch_kudoers(sc, activity_id).await)? else {
+            continue;
+        };
+        let comments = comments.iter().map(|comment| CommentRow::from_comment(activity_id, comment)).collect();
+        let kudos = kudoers.iter().map(|athlete| KudosRow::from_athlete(activity_id, athlete)).collect();
+        save_social(connection(pool).await?, activity_id, comments, kudos).await?;
+        refreshed += 1;
+    }
+    Ok(refreshed)
+}
+
+async fn fetch_comments(sc: &StravaClient, activity_id: i64) -> Result<Vec<Comment>, ClientError> {
+    let mut comments: Vec<Comment> = Vec::new();
+    loop {
+        let cursor = comments.last().and_then(|comment| comment.cursor.clone());
+        let page = sc.get_activity_comments_page(activity_id, PER_PAGE, cursor.as_deref()).await?;
+        let last_page = page.len() < PER_PAGE as usize || page.last().is_none_or(|comment| comment.cursor.is_none());
+        comments.extend(page);
+        if last_page {
+            return Ok(comments);
+        }
+    }
+}
+
+async fn fetch_kudoers(sc: &StravaClient, activity_id: i64) -> Result<Vec<SocialAthlete>, ClientError> {
+    let mut kudoers = Vec::new();
+    let mut page = 1;
+    loop {
+        let athletes = sc.get_activity_kudos_page(activity_id, page, PER_PAGE).await?;
+        let last_page = athletes.len() < PER_PAGE as usize;
+        kudoers.extend(athletes);
+        if last_page {
+            return Ok(kudoers);
+        }
+        page += 1;
+    }
+}
+
+// Fetch the detailed activity for the fields the summary leaves out
+pub async fn sync_activity_details(sc: &StravaClient, pool: &Pool, activity_id: i64) -> Result<(), ApiError> {
+    let raw = sc
+        .get_activity_raw(activity_id)
+        .await
+        .map_err(|e| fetch_error(e, "Could not fetch the detailed activity"))?;
+    let activity = serde_json::from_value::<Activity>(raw).map_err(|_| ApiError {
+        status_code: StatusCode::BAD_GATEWAY,
+        message: "Could not parse the detailed activity".to_string(),
+    })?;
+    // Laps and efforts first, the details mark the activity as synced
+    let laps = activity.laps.iter().map(|lap| LapRow::from_lap(activity_id, lap)).collect();
+    let splits = [("metric", &activity.splits_metric), ("standard", &activity.splits_standard)]
+        .into_iter()
+        .flat_map(|(units, splits)| splits.iter().map(move |split| SplitRow::from_split(activity_id, units, split)))
+        .collect();
+    replace_laps(connection(pool).await?, activity_id, laps, splits).await?;
+
+    let segments = activity.segment_efforts.iter().map(|effort| SegmentRow::from_segment(&effort.segment)).collect();
+    let efforts = activity
+        .segment_efforts
+        .iter()
+        .map(|effort| SegmentEffortRow::from_effort(activity_id, activity.athlete.id, effort))
+        .collect();
+    replace_segment_efforts(connection(pool).await?, activity_id, segments, efforts).await?;
+
+    update_activity_details(
+        connection(pool).await?,
+        activity_id,
+        activity.description,
+        activity.private_note,
+        activity.calories,
+        activity.map.and_then(|map| map.polyline),
+    )
+    .await?;
+    Ok(())
+}
+
+// Fetch the streams of an activity into the blob store. Streams never change once
+// recorded so fetching them again only costs the request, never disk space.
+pub async fn sync_activity_streams(
+    sc: &StravaClient,
+    blob_store: &BlobStore,
+    object_store: Option<&S3Store>,
+    pool: &Pool,
+    activity_id: i64,
+) -> Result<String, ApiError> {
+    let bytes = sc
+        .get_activity_streams_raw(activity_id)
+        .await
+        .map_err(|e| fetch_error(e, "Could not fetch activity streams"))?;
+
+    let hash = blob_store.put(&bytes).map_err(|_| ApiError {
+        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        message: "Could not write blob".to_string(),
+    })?;
+    // One blob per activity, streams strava sends different bytes for let go of the old one
+    let owner = format!("activity:{}:streams", activity_id);
+    replace_blob_ref(connection(pool).await?, owner, hash.clone(), bytes.len()).await?;
+    // The local store serves the analysis, the object store is the off-site copy
+    if let Some(store) = object_store {
+        archive_object(store, pool, "streams", Some(activity_id), &bytes, "application/json").await?;
+    }
+
+    Ok(hash)
+}
+
+// Delete every blob that lost all its references, returns how many went away. A blob
+// referenced within GC_GRACE is kept, a sync running next to the gc may be about to
+// reference it again.
+pub async fn collect_garbage(blob_store: &BlobStore, pool: &Pool) -> Result<usize, ApiError> {
+    let older_than = (Utc::now() - GC_GRACE).naive_utc();
+    let orphans = take_unreferenced_blobs(connection(pool).await?, older_than).await?;
+    for hash in &orphans {
+        blob_store.delete(hash).map_err(|_| ApiError {
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!("Could not delete blob {}", hash),
+        })?;
+    }
+    Ok(orphans.len())
+}